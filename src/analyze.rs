@@ -0,0 +1,251 @@
+use crate::file::{File, Track, TrackType};
+use crate::group::{GroupKey, key_audlang_audname, key_sublang_subname};
+use crate::track_operations::TrackOperation;
+
+use std::path::Path;
+use std::process;
+
+/// Subtitle tracks with less events per minute are considered to be forced subtitles.
+const FORCED_EVENTS_PER_MINUTE: f64 = 1.0;
+
+const FORCED_PATTERNS: [&str; 3] = ["forced", "signs", "foreign"];
+const HEARING_IMPAIRED_PATTERNS: [&str; 4] = ["sdh", "hearing impaired", "cc", "hoh"];
+const COMMENTARY_PATTERNS: [&str; 2] = ["commentary", "director's comments"];
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SuggestedFlag {
+    Forced,
+    HearingImpaired,
+    Commentary,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Suggestion {
+    pub track_no: usize,
+    pub flag: SuggestedFlag,
+    pub value: bool,
+    pub reason: String,
+}
+
+impl Suggestion {
+    pub fn description(&self) -> String {
+        let flag = match self.flag {
+            SuggestedFlag::Forced => "forced",
+            SuggestedFlag::HearingImpaired => "hearing impaired",
+            SuggestedFlag::Commentary => "commentary",
+        };
+        let action = if self.value { "set" } else { "unset" };
+        format!(
+            "Track {}: {} {} flag ({})",
+            self.track_no, action, flag, self.reason
+        )
+    }
+
+    pub fn apply(&self, key: &mut GroupKey) {
+        match self.flag {
+            SuggestedFlag::Forced => key.forced = self.value,
+            SuggestedFlag::HearingImpaired => key.hearing_impaired = self.value,
+            SuggestedFlag::Commentary => key.commentary = self.value,
+        }
+    }
+
+    pub fn to_operation(&self) -> TrackOperation<'static> {
+        match self.flag {
+            SuggestedFlag::Forced => TrackOperation::SetForced(self.value),
+            SuggestedFlag::HearingImpaired => TrackOperation::SetHearingImpaired(self.value),
+            SuggestedFlag::Commentary => TrackOperation::SetCommentary(self.value),
+        }
+    }
+}
+
+fn name_matches(name: &str, patterns: &[&str]) -> Option<String> {
+    let name = name.to_lowercase();
+    let words: Vec<&str> = name
+        .split(|c: char| !c.is_alphanumeric() && c != '\'')
+        .filter(|word| !word.is_empty())
+        .collect();
+    patterns
+        .iter()
+        .find(|pattern| {
+            if pattern.contains(' ') {
+                name.contains(*pattern)
+            } else {
+                words.contains(pattern)
+            }
+        })
+        .map(|pattern| format!("name contains \"{}\"", pattern))
+}
+
+pub fn name_indicates_forced(name: &str) -> bool {
+    name_matches(name, &FORCED_PATTERNS).is_some()
+}
+
+/// Proposes flag corrections for all tracks of the given keys based on the track names.
+pub fn suggest_from_keys(keys: &[GroupKey], track_type: TrackType) -> Vec<Suggestion> {
+    let mut suggestions = Vec::new();
+    for (track_no, key) in keys.iter().enumerate() {
+        let Some(name) = key.name.as_deref() else {
+            continue;
+        };
+        if matches!(track_type, TrackType::Subtitles) {
+            if !key.forced
+                && let Some(reason) = name_matches(name, &FORCED_PATTERNS)
+            {
+                suggestions.push(Suggestion {
+                    track_no,
+                    flag: SuggestedFlag::Forced,
+                    value: true,
+                    reason,
+                });
+            }
+            if !key.hearing_impaired
+                && let Some(reason) = name_matches(name, &HEARING_IMPAIRED_PATTERNS)
+            {
+                suggestions.push(Suggestion {
+                    track_no,
+                    flag: SuggestedFlag::HearingImpaired,
+                    value: true,
+                    reason,
+                });
+            }
+        }
+        if !key.commentary
+            && let Some(reason) = name_matches(name, &COMMENTARY_PATTERNS)
+        {
+            suggestions.push(Suggestion {
+                track_no,
+                flag: SuggestedFlag::Commentary,
+                value: true,
+                reason,
+            });
+        }
+    }
+    suggestions
+}
+
+/// Proposes flag corrections for the tracks of a file. If `count_events` is set,
+/// text subtitles are extracted to decide whether they only cover parts of the file.
+pub fn suggest_for_file(file: &File, track_type: TrackType, count_events: bool) -> Vec<Suggestion> {
+    let keys = match track_type {
+        TrackType::Audio => key_audlang_audname(file),
+        TrackType::Subtitles => key_sublang_subname(file),
+        TrackType::Video => return Vec::new(),
+    };
+    let mut suggestions = suggest_from_keys(&keys, track_type);
+    if !count_events || !matches!(track_type, TrackType::Subtitles) {
+        return suggestions;
+    }
+    let Some(duration) = file.duration() else {
+        return suggestions;
+    };
+    let minutes = duration.as_secs_f64() / 60.0;
+    for (track_no, track) in file.subtitle_tracks.iter().enumerate() {
+        let already_suggested = suggestions
+            .iter()
+            .any(|s| s.track_no == track_no && s.flag == SuggestedFlag::Forced);
+        if track.forced || already_suggested || minutes <= 0.0 {
+            continue;
+        }
+        if let Some(events) = count_subtitle_events(file, track) {
+            let per_minute = events as f64 / minutes;
+            if per_minute < FORCED_EVENTS_PER_MINUTE {
+                suggestions.push(Suggestion {
+                    track_no,
+                    flag: SuggestedFlag::Forced,
+                    value: true,
+                    reason: format!("only {} events in {:.0} minutes", events, minutes),
+                });
+            }
+        }
+    }
+    suggestions
+}
+
+/// Extracts a text subtitle track with mkvextract and counts its events.
+pub fn count_subtitle_events(file: &File, track: &Track) -> Option<usize> {
+    if track.text_subtitles != Some(true) {
+        return None;
+    }
+    let out_path =
+        std::env::temp_dir().join(format!("mtxstuff-{}-{}.sub", process::id(), track.id));
+    let status = process::Command::new("mkvextract")
+        .arg(Path::new(&file.file_name))
+        .arg("tracks")
+        .arg(format!("{}:{}", track.id, out_path.display()))
+        .output()
+        .ok()?
+        .status;
+    let content = std::fs::read(&out_path);
+    let _ = std::fs::remove_file(&out_path);
+    if !status.success() {
+        return None;
+    }
+    let content = String::from_utf8_lossy(&content.ok()?).into_owned();
+    Some(count_events(&content))
+}
+
+/// Counts the events of SRT (timestamp lines) or SSA/ASS (dialogue lines) subtitles.
+fn count_events(content: &str) -> usize {
+    let dialogues = content
+        .lines()
+        .filter(|line| line.starts_with("Dialogue:"))
+        .count();
+    if dialogues > 0 {
+        dialogues
+    } else {
+        content
+            .lines()
+            .filter(|line| line.contains(" --> "))
+            .count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(name: &str, forced: bool) -> GroupKey {
+        GroupKey {
+            language: Some("eng".to_owned()),
+            name: Some(name.to_owned()),
+            default: false,
+            forced,
+            enabled: true,
+            hearing_impaired: false,
+            commentary: false,
+        }
+    }
+
+    #[test]
+    fn test_suggest_from_keys() {
+        let keys = vec![
+            key("English", false),
+            key("English (Forced)", false),
+            key("English [SDH]", false),
+            key("Forced", true),
+            key("Director's Commentary", false),
+            key("Soccer", false),
+        ];
+        let suggestions = suggest_from_keys(&keys, TrackType::Subtitles);
+        let found: Vec<(usize, SuggestedFlag)> =
+            suggestions.iter().map(|s| (s.track_no, s.flag)).collect();
+        assert_eq!(
+            found,
+            vec![
+                (1, SuggestedFlag::Forced),
+                (2, SuggestedFlag::HearingImpaired),
+                (4, SuggestedFlag::Commentary),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_count_events() {
+        let srt =
+            "1\n00:00:01,000 --> 00:00:02,000\nHello\n\n2\n00:00:03,000 --> 00:00:04,000\nWorld\n";
+        assert_eq!(count_events(srt), 2);
+        let ass =
+            "[Events]\nFormat: Layer, Start, End, Text\nDialogue: 0,0:00:01.00,0:00:02.00,Hi\n";
+        assert_eq!(count_events(ass), 1);
+    }
+}
@@ -1,7 +1,11 @@
-use std::process::{self, ExitStatus};
+use std::io::Read;
+use std::process::{self, ExitStatus, Stdio};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc;
 use std::thread;
 use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 
 #[derive(Debug, Clone)]
 pub struct CommandOutput {
@@ -54,6 +58,11 @@ impl Command {
         message
     }
 
+    /// The file the command works on, which is always the last argument.
+    pub fn file_name(&self) -> Option<&str> {
+        self.arguments.last().map(String::as_str)
+    }
+
     pub fn to_cmd_string(&self) -> Option<String> {
         // Do not check this here because command is supposed to be universal
         if self.arguments.is_empty() {
@@ -75,24 +84,64 @@ impl Command {
     }
 
     pub fn run(&mut self) -> std::io::Result<()> {
-        let mut command = process::Command::new(&self.executable);
-        let command = command.args(&self.arguments);
-        //println!("Running command {:?}", command);
-        let output = command.output()?;
+        self.run_killable(&AtomicBool::new(false))
+    }
+
+    /// Runs the command and kills the process as soon as `kill` is set.
+    pub fn run_killable(&mut self, kill: &AtomicBool) -> std::io::Result<()> {
+        let mut child = process::Command::new(&self.executable)
+            .args(&self.arguments)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        // The pipes are read while waiting, a full pipe would block the process
+        let stdout = read_pipe(child.stdout.take());
+        let stderr = read_pipe(child.stderr.take());
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if kill.load(Ordering::Relaxed) {
+                child.kill()?;
+                break child.wait()?;
+            }
+            thread::sleep(Duration::from_millis(20));
+        };
         self.output = Some(CommandOutput {
-            status: output.status,
-            stdout: String::from_utf8(output.stdout).expect("commmand should return UTF8 data"),
-            stderr: String::from_utf8(output.stderr).expect("commmand should return UTF8 data"),
+            status,
+            stdout: String::from_utf8_lossy(&stdout.join().unwrap()?).into_owned(),
+            stderr: String::from_utf8_lossy(&stderr.join().unwrap()?).into_owned(),
         });
         Ok(())
     }
 }
 
+fn read_pipe(pipe: Option<impl Read + Send + 'static>) -> JoinHandle<std::io::Result<Vec<u8>>> {
+    thread::spawn(move || {
+        let mut content = Vec::new();
+        if let Some(mut pipe) = pipe {
+            pipe.read_to_end(&mut content)?;
+        }
+        Ok(content)
+    })
+}
+
+enum CommandEvent {
+    Started(usize),
+    Finished(std::io::Result<Command>),
+}
+
 pub(crate) struct CommandHandler {
     command_thread: JoinHandle<()>,
-    result_receiver: mpsc::Receiver<std::io::Result<Command>>,
+    event_receiver: mpsc::Receiver<CommandEvent>,
     done_commands: Vec<std::io::Result<Command>>,
-    num_commands: usize,
+    /// The file of each command, used to show what is running.
+    file_names: Vec<String>,
+    running: Option<usize>,
+    started: Instant,
+    cancelled: Arc<AtomicBool>,
+    killed: Arc<AtomicBool>,
 }
 
 #[derive(Clone, Copy)]
@@ -104,55 +153,137 @@ pub(crate) enum CommandHandlerStatus {
 impl CommandHandler {
     pub(crate) fn new(commands: Vec<Command>) -> Self {
         let (tx_cmd, rx_cmd): (mpsc::Sender<Command>, mpsc::Receiver<Command>) = mpsc::channel();
-        let (tx_res, rx_res): (
-            mpsc::Sender<std::io::Result<Command>>,
-            mpsc::Receiver<std::io::Result<Command>>,
-        ) = mpsc::channel();
+        let (tx_event, rx_event): (mpsc::Sender<CommandEvent>, mpsc::Receiver<CommandEvent>) =
+            mpsc::channel();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let killed = Arc::new(AtomicBool::new(false));
+        let (thread_cancelled, thread_killed) = (cancelled.clone(), killed.clone());
         let command_thread = thread::spawn(move || {
+            let mut idx = 0;
             loop {
                 let task = rx_cmd.recv();
                 match task {
+                    // Cancelled commands are returned without output
+                    Ok(command) if thread_cancelled.load(Ordering::Relaxed) => {
+                        tx_event.send(CommandEvent::Finished(Ok(command))).unwrap();
+                        idx += 1;
+                    }
                     Ok(mut command) => {
-                        match command.run() {
-                            Ok(_) => tx_res.send(Ok(command)).unwrap(),
-                            Err(err) => tx_res.send(Err(err)).unwrap(),
+                        tx_event.send(CommandEvent::Started(idx)).unwrap();
+                        match command.run_killable(&thread_killed) {
+                            Ok(_) => tx_event.send(CommandEvent::Finished(Ok(command))).unwrap(),
+                            Err(err) => tx_event.send(CommandEvent::Finished(Err(err))).unwrap(),
                         };
+                        idx += 1;
                     }
                     Err(_) => break, // producer is gone, we are done,
                 }
             }
         });
-        let num_commands = commands.len();
+        let file_names = commands
+            .iter()
+            .map(|command| {
+                command
+                    .file_name()
+                    .unwrap_or(&command.executable)
+                    .to_string()
+            })
+            .collect();
         for command in commands {
             tx_cmd.send(command).unwrap();
         }
 
         Self {
             command_thread,
-            result_receiver: rx_res,
+            event_receiver: rx_event,
             done_commands: Default::default(),
-            num_commands,
+            file_names,
+            running: None,
+            started: Instant::now(),
+            cancelled,
+            killed,
         }
     }
 
+    /// Drops the commands that have not started yet, the running command finishes.
+    pub(crate) fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Cancels the remaining commands and kills the running one.
+    pub(crate) fn kill(&self) {
+        self.cancel();
+        self.killed.store(true, Ordering::Relaxed);
+    }
+
+    pub(crate) fn cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    fn num_commands(&self) -> usize {
+        self.file_names.len()
+    }
+
     pub(crate) fn check(&mut self) -> CommandHandlerStatus {
-        while let Ok(received) = self.result_receiver.try_recv() {
-            self.done_commands.push(received)
+        while let Ok(received) = self.event_receiver.try_recv() {
+            match received {
+                CommandEvent::Started(idx) => self.running = Some(idx),
+                CommandEvent::Finished(result) => {
+                    self.running = None;
+                    self.done_commands.push(result)
+                }
+            }
         }
-        if self.num_commands == self.done_commands.len() {
+        if self.num_commands() == self.done_commands.len() {
             CommandHandlerStatus::Done
         } else {
-            let ratio = self.done_commands.len() as f64 / self.num_commands as f64;
+            let ratio = self.done_commands.len() as f64 / self.num_commands() as f64;
             CommandHandlerStatus::Percent((ratio * 100f64).round() as u16)
         }
     }
 
+    /// Results of the commands that finished so far, in the order they were run.
+    pub(crate) fn done_commands(&self) -> &[std::io::Result<Command>] {
+        &self.done_commands
+    }
+
+    /// Number of finished commands and number of all commands.
+    pub(crate) fn progress(&self) -> (usize, usize) {
+        (self.done_commands.len(), self.num_commands())
+    }
+
+    /// The file of the command that is running right now.
+    pub(crate) fn running(&self) -> Option<&str> {
+        self.running.map(|idx| self.file_names[idx].as_str())
+    }
+
+    pub(crate) fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Estimated time until all commands are done, assuming the remaining commands take as
+    /// long as the finished ones on average.
+    pub(crate) fn eta(&self) -> Option<Duration> {
+        estimate_remaining(
+            self.elapsed(),
+            self.done_commands.len(),
+            self.num_commands(),
+        )
+    }
+
     pub(crate) fn into_results(self) -> Vec<std::io::Result<Command>> {
         self.command_thread.join().unwrap();
         self.done_commands
     }
 }
 
+fn estimate_remaining(elapsed: Duration, done: usize, total: usize) -> Option<Duration> {
+    if done == 0 {
+        return None;
+    }
+    Some(elapsed.mul_f64((total - done) as f64 / done as f64))
+}
+
 #[cfg(test)]
 mod tests {
     // Note this useful idiom: importing names from outer (for mod tests) scope.
@@ -180,10 +311,59 @@ mod tests {
             }
             thread::sleep(std::time::Duration::from_millis(100));
         }
+        assert_eq!(command_handler.progress(), (5, 5));
+        assert_eq!(command_handler.running(), None);
+        assert_eq!(command_handler.eta(), Some(Duration::ZERO));
         for command in command_handler.into_results() {
             let command = command.expect("done without error");
             let output = command.output.expect("command has run so there is output");
             assert!(output.status.success());
         }
     }
+
+    #[test]
+    fn test_estimate_remaining() {
+        let elapsed = Duration::from_secs(30);
+        assert_eq!(estimate_remaining(elapsed, 0, 4), None);
+        assert_eq!(
+            estimate_remaining(elapsed, 1, 4),
+            Some(Duration::from_secs(90))
+        );
+        assert_eq!(estimate_remaining(elapsed, 4, 4), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn test_command_handler_cancel() {
+        let mut commands = Vec::<Command>::new();
+        for _ in 0..3 {
+            let mut command = Command::new("sleep");
+            command.arguments.push("10".to_string());
+            commands.push(command);
+        }
+
+        let mut command_handler = CommandHandler::new(commands);
+        while command_handler.running().is_none() {
+            command_handler.check();
+            thread::sleep(std::time::Duration::from_millis(10));
+        }
+        command_handler.kill();
+        while let CommandHandlerStatus::Percent(_) = command_handler.check() {
+            thread::sleep(std::time::Duration::from_millis(10));
+        }
+        assert!(command_handler.elapsed() < Duration::from_secs(10));
+        let results = command_handler.into_results();
+        let outputs: Vec<_> = results
+            .into_iter()
+            .map(|command| command.expect("done without error").output)
+            .collect();
+        assert!(
+            !outputs[0]
+                .as_ref()
+                .expect("first command ran")
+                .status
+                .success()
+        );
+        assert!(outputs[1].is_none());
+        assert!(outputs[2].is_none());
+    }
 }
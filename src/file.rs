@@ -3,7 +3,7 @@ use std::process;
 
 use serde_json::Value;
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum TrackType {
     Video,
     Audio,
@@ -15,6 +15,8 @@ pub enum Flag {
     Default,
     Forced,
     Enabled,
+    HearingImpaired,
+    Commentary,
 }
 
 #[derive(Debug)]
@@ -26,6 +28,14 @@ pub struct Track {
     pub default: bool,
     pub forced: bool,
     pub enabled: bool,
+    pub hearing_impaired: bool,
+    pub commentary: bool,
+    pub codec: Option<String>,
+    pub codec_id: Option<String>,
+    pub audio_channels: Option<i64>,
+    pub audio_sampling_frequency: Option<i64>,
+    pub bitrate: Option<i64>,
+    pub text_subtitles: Option<bool>,
 }
 
 #[derive(Debug)]
@@ -103,6 +113,39 @@ impl File {
             json,
         })
     }
+
+    /// Audio-only files like .mka have no video tracks.
+    pub fn is_audio_only(&self) -> bool {
+        self.video_tracks.is_empty() && !self.audio_tracks.is_empty()
+    }
+
+    /// File name with a marker for files without video tracks.
+    pub fn display_name(&self) -> String {
+        if self.is_audio_only() {
+            format!("{} [audio only]", self.file_name)
+        } else {
+            self.file_name.clone()
+        }
+    }
+
+    /// Duration of the file as reported by the container.
+    pub fn duration(&self) -> Option<std::time::Duration> {
+        let nanos = self
+            .json
+            .get("container")?
+            .get("properties")?
+            .get("duration")?
+            .as_u64()?;
+        Some(std::time::Duration::from_nanos(nanos))
+    }
+
+    pub fn tracks(&self, track_type: TrackType) -> &[Track] {
+        match track_type {
+            TrackType::Video => &self.video_tracks,
+            TrackType::Audio => &self.audio_tracks,
+            TrackType::Subtitles => &self.subtitle_tracks,
+        }
+    }
 }
 
 impl Track {
@@ -121,6 +164,30 @@ impl Track {
         let default = properties.get("default_track")?.as_bool()?;
         let forced = properties.get("forced_track")?.as_bool()?;
         let enabled = properties.get("enabled_track")?.as_bool()?;
+        // Added in Matroska v4, older mkvmerge versions do not report these flags
+        let hearing_impaired = properties
+            .get("flag_hearing_impaired")
+            .and_then(|f| f.as_bool())
+            .unwrap_or(false);
+        let commentary = properties
+            .get("flag_commentary")
+            .and_then(|f| f.as_bool())
+            .unwrap_or(false);
+        let codec = json.get("codec").and_then(|c| c.as_str()).map(String::from);
+        let codec_id = properties
+            .get("codec_id")
+            .and_then(|c| c.as_str())
+            .map(String::from);
+        let audio_channels = properties.get("audio_channels").and_then(|c| c.as_i64());
+        let audio_sampling_frequency = properties
+            .get("audio_sampling_frequency")
+            .and_then(|f| f.as_i64());
+        // Only present if the file has statistics tags, the value is stored as a string
+        let bitrate = properties
+            .get("tag_bps")
+            .and_then(|b| b.as_str())
+            .and_then(|b| b.parse::<i64>().ok());
+        let text_subtitles = properties.get("text_subtitles").and_then(|t| t.as_bool());
         let id = json.get("id")?.as_i64()?;
         let ttype = json.get("type")?.as_str()?;
         let ttype = match ttype {
@@ -137,6 +204,84 @@ impl Track {
             default,
             forced,
             enabled,
+            hearing_impaired,
+            commentary,
+            codec,
+            codec_id,
+            audio_channels,
+            audio_sampling_frequency,
+            bitrate,
+            text_subtitles,
         })
     }
+
+    /// Channel layout in the usual notation, e.g. "5.1" for six channels. mkvmerge only
+    /// reports the number of channels, counts with several common layouts are shown as "3ch".
+    pub fn channels_string(&self) -> Option<String> {
+        let channels = self.audio_channels?;
+        Some(match channels {
+            1 => "1.0".to_owned(),
+            2 => "2.0".to_owned(),
+            6 => "5.1".to_owned(),
+            8 => "7.1".to_owned(),
+            _ => format!("{}ch", channels),
+        })
+    }
+
+    pub fn sampling_frequency_string(&self) -> Option<String> {
+        let frequency = self.audio_sampling_frequency?;
+        Some(format!("{} kHz", frequency as f64 / 1000.0))
+    }
+
+    pub fn bitrate_string(&self) -> Option<String> {
+        let bitrate = self.bitrate?;
+        Some(format!("{} kb/s", bitrate / 1000))
+    }
+
+    /// Whether a subtitle track is text based (SRT, ASS) or image based (PGS, VobSub).
+    pub fn subtitle_kind(&self) -> Option<&'static str> {
+        let text = self.text_subtitles?;
+        Some(if text { "text" } else { "image" })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_track_from_json() {
+        let json: Value = serde_json::from_str(
+            r#"{
+                "codec": "DTS-HD Master Audio",
+                "id": 1,
+                "properties": {
+                    "audio_channels": 8,
+                    "audio_sampling_frequency": 48000,
+                    "codec_id": "A_DTS",
+                    "default_track": true,
+                    "enabled_track": true,
+                    "forced_track": false,
+                    "language": "eng",
+                    "tag_bps": "4096000",
+                    "track_name": "Surround 7.1"
+                },
+                "type": "audio"
+            }"#,
+        )
+        .unwrap();
+        let track = Track::from_json(&json).expect("track can be parsed");
+        assert_eq!(track.codec.as_deref(), Some("DTS-HD Master Audio"));
+        assert_eq!(track.codec_id.as_deref(), Some("A_DTS"));
+        assert_eq!(track.channels_string().as_deref(), Some("7.1"));
+        assert_eq!(track.sampling_frequency_string().as_deref(), Some("48 kHz"));
+        assert_eq!(track.bitrate_string().as_deref(), Some("4096 kb/s"));
+        assert_eq!(track.subtitle_kind(), None);
+        // 3.0 or 2.1, the layout is not known
+        let track = Track {
+            audio_channels: Some(3),
+            ..track
+        };
+        assert_eq!(track.channels_string().as_deref(), Some("3ch"));
+    }
 }
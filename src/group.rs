@@ -1,8 +1,11 @@
 use super::command::Command;
-use super::file::{File, TrackType};
+use super::file::{File, Track, TrackType};
 use super::table::Table;
-use crate::track_operations::{TrackOperation, TrackOperations};
+use crate::track_operations::{EditPlan, FileOperation, TrackOperation, TrackOperations};
+use anyhow::{Context, Result, bail};
 use itertools::Itertools;
+use std::cmp::Reverse;
+use std::path::{Path, PathBuf};
 
 pub fn key_sublang_subname(file: &File) -> Vec<GroupKey> {
     file.subtitle_tracks
@@ -15,6 +18,8 @@ pub fn key_sublang_subname(file: &File) -> Vec<GroupKey> {
                 default: track.default,
                 forced: track.forced,
                 enabled: track.enabled,
+                hearing_impaired: track.hearing_impaired,
+                commentary: track.commentary,
             }
         })
         .collect()
@@ -29,6 +34,8 @@ pub fn key_audlang_audname(file: &File) -> Vec<GroupKey> {
             default: track.default,
             forced: track.forced,
             enabled: track.enabled,
+            hearing_impaired: track.hearing_impaired,
+            commentary: track.commentary,
         })
         .collect()
 }
@@ -40,8 +47,76 @@ pub struct Group<'a> {
 }
 
 impl<'a> Group<'a> {
-    pub fn apply_changes(&self, keys: &[GroupKey], track_type: TrackType) -> Vec<Command> {
+    pub fn apply_changes(
+        &self,
+        keys: &[GroupKey],
+        track_type: TrackType,
+        file_ops: &[FileOperation],
+    ) -> Vec<Command> {
+        let mut plan = EditPlan::new();
+        plan.add_track_ops(&self.track_changes(keys, track_type));
+        for file_op in file_ops {
+            plan.add_file_op(file_op.clone());
+        }
+        self.files
+            .iter()
+            .map(|file| plan.generate_command(file))
+            .collect()
+    }
+
+    /// Identifier derived from the track layout of the group. Unlike the group number it
+    /// does not change when other groups appear or disappear.
+    pub fn id(&self) -> String {
+        group_id(&self.key)
+    }
+
+    /// Languages of the tracks in track order without duplicates, "und" if not set.
+    pub fn languages(&self) -> Vec<&str> {
+        self.key
+            .iter()
+            .map(|key| key.language.as_deref().unwrap_or("und"))
+            .unique()
+            .collect()
+    }
+
+    /// Deepest directory containing all files of the group.
+    pub fn common_directory(&self) -> Option<PathBuf> {
+        let mut dirs = self
+            .files
+            .iter()
+            .filter_map(|file| Path::new(&file.file_name).parent());
+        let mut common = dirs.next()?.to_path_buf();
+        for dir in dirs {
+            while !dir.starts_with(&common) {
+                if !common.pop() {
+                    return None;
+                }
+            }
+        }
+        Some(common)
+    }
+
+    /// Operations that change the tracks of the files from the group key to `keys`.
+    pub fn track_changes<'k>(
+        &self,
+        keys: &'k [GroupKey],
+        track_type: TrackType,
+    ) -> TrackOperations<'k> {
         let mut ops = TrackOperations::new(track_type);
+        // A single default track is set with one operation that clears the other tracks
+        let defaults_changed = self
+            .key
+            .iter()
+            .zip(keys.iter())
+            .any(|(cur, changed)| cur.default != changed.default);
+        let new_defaults: Vec<usize> = keys.iter().positions(|changed| changed.default).collect();
+        let exclusive_default = defaults_changed && new_defaults.len() == 1;
+        if exclusive_default {
+            ops.add(
+                new_defaults[0] as i64,
+                TrackOperation::SetDefaultExclusive(true),
+            );
+        }
         self.key
             .iter()
             .zip(keys.iter())
@@ -62,7 +137,7 @@ impl<'a> Group<'a> {
                         TrackOperation::SetTitle(changed.name.as_deref()),
                     )
                 }
-                if cur.default != changed.default {
+                if cur.default != changed.default && !exclusive_default {
                     ops.add(idx as i64, TrackOperation::SetDefault(changed.default));
                 }
                 if cur.forced != changed.forced {
@@ -71,13 +146,104 @@ impl<'a> Group<'a> {
                 if cur.enabled != changed.enabled {
                     ops.add(idx as i64, TrackOperation::SetEnabled(changed.enabled));
                 }
+                if cur.hearing_impaired != changed.hearing_impaired {
+                    ops.add(
+                        idx as i64,
+                        TrackOperation::SetHearingImpaired(changed.hearing_impaired),
+                    );
+                }
+                if cur.commentary != changed.commentary {
+                    ops.add(
+                        idx as i64,
+                        TrackOperation::SetCommentary(changed.commentary),
+                    );
+                }
             });
-        let cmds: Vec<_> = self
-            .files
-            .iter()
-            .map(|file| ops.generate_command(file))
-            .collect();
-        cmds
+        ops
+    }
+
+    /// Read-only track information of this group, fields that differ
+    /// between the files of the group are shown as "mixed".
+    pub fn track_infos(&self, track_type: TrackType) -> Vec<TrackInfo> {
+        let mut infos: Vec<TrackInfo> = Vec::new();
+        for (file_idx, file) in self.files.iter().enumerate() {
+            for (idx, track) in file.tracks(track_type).iter().enumerate() {
+                let info = TrackInfo::from_track(track);
+                if file_idx == 0 {
+                    infos.push(info);
+                } else if let Some(existing) = infos.get_mut(idx) {
+                    existing.merge(&info);
+                }
+            }
+        }
+        infos
+    }
+}
+
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct TrackInfo {
+    pub codec: Option<String>,
+    pub codec_id: Option<String>,
+    pub channels: Option<String>,
+    pub sampling_frequency: Option<String>,
+    pub bitrate: Option<String>,
+    pub subtitle_kind: Option<String>,
+}
+
+impl TrackInfo {
+    pub fn from_track(track: &Track) -> Self {
+        TrackInfo {
+            codec: track.codec.clone(),
+            codec_id: track.codec_id.clone(),
+            channels: track.channels_string(),
+            sampling_frequency: track.sampling_frequency_string(),
+            bitrate: track.bitrate_string(),
+            subtitle_kind: track.subtitle_kind().map(String::from),
+        }
+    }
+
+    fn merge(&mut self, other: &TrackInfo) {
+        let merge_field = |field: &mut Option<String>, other: &Option<String>| {
+            if field != other {
+                *field = Some("mixed".to_owned());
+            }
+        };
+        merge_field(&mut self.codec, &other.codec);
+        merge_field(&mut self.codec_id, &other.codec_id);
+        merge_field(&mut self.channels, &other.channels);
+        merge_field(&mut self.sampling_frequency, &other.sampling_frequency);
+        merge_field(&mut self.bitrate, &other.bitrate);
+        merge_field(&mut self.subtitle_kind, &other.subtitle_kind);
+    }
+
+    pub fn headers(track_type: TrackType) -> Vec<&'static str> {
+        match track_type {
+            TrackType::Audio => vec!["codec", "codec id", "ch", "rate", "bitrate"],
+            TrackType::Subtitles => vec!["codec", "codec id", "kind"],
+            TrackType::Video => vec!["codec", "codec id", "bitrate"],
+        }
+    }
+
+    pub fn row(&self, track_type: TrackType) -> Vec<Option<String>> {
+        match track_type {
+            TrackType::Audio => vec![
+                self.codec.clone(),
+                self.codec_id.clone(),
+                self.channels.clone(),
+                self.sampling_frequency.clone(),
+                self.bitrate.clone(),
+            ],
+            TrackType::Subtitles => vec![
+                self.codec.clone(),
+                self.codec_id.clone(),
+                self.subtitle_kind.clone(),
+            ],
+            TrackType::Video => vec![
+                self.codec.clone(),
+                self.codec_id.clone(),
+                self.bitrate.clone(),
+            ],
+        }
     }
 }
 
@@ -88,11 +254,13 @@ pub struct GroupKey {
     pub default: bool,
     pub forced: bool,
     pub enabled: bool,
+    pub hearing_impaired: bool,
+    pub commentary: bool,
 }
 
 impl GroupKey {
     pub fn headers(&self) -> Vec<&str> {
-        vec!["lang", "name", "def", "fcd", "en"]
+        vec!["lang", "name", "def", "fcd", "en", "sdh", "com"]
     }
 
     pub fn row(&self) -> Vec<String> {
@@ -113,18 +281,98 @@ impl GroupKey {
         } else {
             "[ ]".to_owned()
         };
-        vec![language, name, default, forced, enabled]
+        let hearing_impaired = if self.hearing_impaired {
+            "[x]".to_owned()
+        } else {
+            "[ ]".to_owned()
+        };
+        let commentary = if self.commentary {
+            "[x]".to_owned()
+        } else {
+            "[ ]".to_owned()
+        };
+        vec![
+            language,
+            name,
+            default,
+            forced,
+            enabled,
+            hearing_impaired,
+            commentary,
+        ]
     }
 }
 
-pub fn print_groupkeys(keys: &[GroupKey]) {
-    let data: Vec<Vec<String>> = keys.iter().map(|gk| gk.row()).collect();
-    let mut table = Table::new(data.iter().map(AsRef::as_ref), &keys[0].headers());
+pub fn print_groupkeys(keys: &[GroupKey], infos: &[TrackInfo], track_type: TrackType) {
+    let data: Vec<Vec<String>> = keys
+        .iter()
+        .zip(infos.iter())
+        .map(|(gk, info)| {
+            let mut row = gk.row();
+            row.extend(
+                info.row(track_type)
+                    .into_iter()
+                    .map(|value| value.unwrap_or_else(|| String::from("-"))),
+            );
+            row
+        })
+        .collect();
+    let mut headers = keys[0].headers();
+    headers.extend(TrackInfo::headers(track_type));
+    let mut table = Table::new(data.iter().map(AsRef::as_ref), &headers);
     let line_numbers: Box<[usize]> = (0..table.lines.len()).collect();
     table.insert_column(0, "#", &line_numbers);
     table.print();
 }
 
+/// FNV-1a hash of the keys, written out by hand because the hashers of std are not
+/// guaranteed to be stable between releases.
+pub fn group_id(keys: &[GroupKey]) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    let mut feed = |bytes: &[u8]| {
+        for byte in bytes {
+            hash ^= *byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    };
+    for key in keys {
+        for text in [&key.language, &key.name] {
+            match text {
+                Some(text) => {
+                    feed(&[1]);
+                    feed(&(text.len() as u64).to_le_bytes());
+                    feed(text.as_bytes());
+                }
+                None => feed(&[0]),
+            }
+        }
+        feed(&[
+            key.default as u8,
+            key.forced as u8,
+            key.enabled as u8,
+            key.hearing_impaired as u8,
+            key.commentary as u8,
+        ]);
+    }
+    format!("{:016x}", hash)
+}
+
+/// Finds a group by its id, numbers of the listing are not accepted. Several groups with the
+/// same id are an error instead of picking one of them.
+pub fn find_group<'g, 'a>(groups: &'g [Group<'a>], id: &str) -> Result<&'g Group<'a>> {
+    let mut matches = groups.iter().filter(|group| group.id() == id);
+    let group = matches
+        .next()
+        .with_context(|| format!("No group with id {}", id))?;
+    if matches.next().is_some() {
+        bail!(
+            "Ambiguous group id {}, select the group with --group-no",
+            id
+        );
+    }
+    Ok(group)
+}
+
 pub fn groupby(files: &[File], key_func: fn(&File) -> Vec<GroupKey>) -> Vec<Group<'_>> {
     let mut files_temp: Vec<&File> = files.iter().collect();
     files_temp.sort_by_key(|ident| key_func(ident));
@@ -139,23 +387,242 @@ pub fn groupby(files: &[File], key_func: fn(&File) -> Vec<GroupKey>) -> Vec<Grou
     groups
 }
 
-pub fn print_groups(groups: &[Group], selected: bool) {
+/// Order of the groups. Groups that compare equal keep the order of their keys, so the
+/// group numbers are the same every time the same files are sorted the same way.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum GroupSort {
+    #[default]
+    Key,
+    /// Groups with the most files first.
+    Files,
+    Directory,
+    Languages,
+    /// Groups with the most files with lint findings first.
+    Problems,
+}
+
+impl GroupSort {
+    pub const ALL: [GroupSort; 5] = [
+        GroupSort::Key,
+        GroupSort::Files,
+        GroupSort::Directory,
+        GroupSort::Languages,
+        GroupSort::Problems,
+    ];
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|sort| sort.id() == id)
+    }
+
+    pub fn id(&self) -> &'static str {
+        match self {
+            GroupSort::Key => "key",
+            GroupSort::Files => "files",
+            GroupSort::Directory => "directory",
+            GroupSort::Languages => "languages",
+            GroupSort::Problems => "problems",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        let pos = Self::ALL.iter().position(|sort| sort == self).unwrap_or(0);
+        Self::ALL[(pos + 1) % Self::ALL.len()]
+    }
+}
+
+/// Order of the files within a group.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum FileSort {
+    #[default]
+    Name,
+    /// Most recently modified files first.
+    Modified,
+    /// Largest files first.
+    Size,
+}
+
+impl FileSort {
+    pub const ALL: [FileSort; 3] = [FileSort::Name, FileSort::Modified, FileSort::Size];
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|sort| sort.id() == id)
+    }
+
+    pub fn id(&self) -> &'static str {
+        match self {
+            FileSort::Name => "name",
+            FileSort::Modified => "mtime",
+            FileSort::Size => "size",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        let pos = Self::ALL.iter().position(|sort| sort == self).unwrap_or(0);
+        Self::ALL[(pos + 1) % Self::ALL.len()]
+    }
+}
+
+/// Sorts groups returned by `groupby` and the files of each group. `has_problems` tells
+/// whether a file has lint findings, it is only called for `GroupSort::Problems`.
+pub fn sort_groups(
+    groups: &mut [Group],
+    group_sort: GroupSort,
+    file_sort: FileSort,
+    has_problems: impl Fn(&File) -> bool,
+) {
+    // The sorts are stable, so equal groups stay in key order
+    match group_sort {
+        GroupSort::Key => {}
+        GroupSort::Files => groups.sort_by_key(|group| Reverse(group.files.len())),
+        GroupSort::Directory => groups.sort_by_cached_key(|group| group.common_directory()),
+        GroupSort::Languages => groups.sort_by_cached_key(|group| {
+            group
+                .languages()
+                .into_iter()
+                .map(str::to_owned)
+                .collect::<Vec<_>>()
+        }),
+        GroupSort::Problems => groups.sort_by_cached_key(|group| {
+            Reverse(group.files.iter().filter(|file| has_problems(file)).count())
+        }),
+    }
+    for group in groups.iter_mut() {
+        match file_sort {
+            FileSort::Name => group.files.sort_by(|a, b| a.file_name.cmp(&b.file_name)),
+            FileSort::Modified => group.files.sort_by_cached_key(|file| {
+                Reverse(
+                    std::fs::metadata(&file.file_name)
+                        .and_then(|metadata| metadata.modified())
+                        .ok(),
+                )
+            }),
+            FileSort::Size => group.files.sort_by_cached_key(|file| {
+                Reverse(
+                    std::fs::metadata(&file.file_name)
+                        .map(|metadata| metadata.len())
+                        .ok(),
+                )
+            }),
+        }
+    }
+}
+
+pub fn print_groups(groups: &[Group], selected: bool, track_type: TrackType) {
     for (idx, group) in groups.iter().enumerate() {
         //println!("Group with key {:?}", group.key);
-        if !selected {
-            println!("---Group {}---", idx);
+        if selected {
+            println!("---Group {}---", group.id());
+        } else {
+            println!("---Group {} (id {})---", idx, group.id());
         }
         println!("Keys for this group are:");
         if !group.key.is_empty() {
-            print_groupkeys(&group.key);
+            print_groupkeys(&group.key, &group.track_infos(track_type), track_type);
         } else {
             println!("Empty");
         }
         println!("Files in this group are");
         for elem in &group.files {
-            println!("    {:?}", elem.file_name);
+            println!("    {:?}", elem.display_name());
         }
         println!();
         println!();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(language: Option<&str>, name: Option<&str>, default: bool) -> GroupKey {
+        GroupKey {
+            language: language.map(str::to_owned),
+            name: name.map(str::to_owned),
+            default,
+            forced: false,
+            enabled: true,
+            hearing_impaired: false,
+            commentary: false,
+        }
+    }
+
+    #[test]
+    fn test_group_id() {
+        let keys = vec![key(Some("jpn"), None, true), key(Some("eng"), None, false)];
+        let id = group_id(&keys);
+        // Users keep ids in scripts, the hash must not change
+        assert_eq!(id, "54c7682b6473b94e");
+        assert_eq!(id, group_id(&keys.clone()));
+        assert_ne!(
+            id,
+            group_id(&[key(Some("jpn"), None, false), key(Some("eng"), None, true)])
+        );
+        assert_ne!(
+            group_id(&[key(None, Some(""), false)]),
+            group_id(&[key(Some(""), None, false)])
+        );
+    }
+
+    fn file(file_name: &str, language: &str) -> File {
+        File::from_json_str(&format!(
+            r#"{{
+                "file_name": "{}",
+                "tracks": [
+                    {{"id": 0, "type": "audio", "properties": {{"language": "{}",
+                        "default_track": true, "forced_track": false, "enabled_track": true}}}}
+                ]
+            }}"#,
+            file_name, language
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_sort_groups() {
+        let files = vec![
+            file("b/2.mkv", "jpn"),
+            file("b/1.mkv", "jpn"),
+            file("a/3.mkv", "eng"),
+            file("c/4.mkv", "ger"),
+        ];
+        let sorted = |group_sort| {
+            let mut groups = groupby(&files, key_audlang_audname);
+            sort_groups(&mut groups, group_sort, FileSort::Name, |file| {
+                file.file_name == "c/4.mkv"
+            });
+            groups
+                .iter()
+                .map(|group| group.languages().join(","))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(sorted(GroupSort::Key), ["eng", "ger", "jpn"]);
+        assert_eq!(sorted(GroupSort::Files), ["jpn", "eng", "ger"]);
+        assert_eq!(sorted(GroupSort::Directory), ["eng", "jpn", "ger"]);
+        assert_eq!(sorted(GroupSort::Problems), ["ger", "eng", "jpn"]);
+
+        let mut groups = groupby(&files, key_audlang_audname);
+        sort_groups(&mut groups, GroupSort::Files, FileSort::Name, |_| false);
+        let file_names: Vec<&str> = groups[0]
+            .files
+            .iter()
+            .map(|file| file.file_name.as_str())
+            .collect();
+        assert_eq!(file_names, ["b/1.mkv", "b/2.mkv"]);
+    }
+
+    #[test]
+    fn test_find_group() {
+        let files = vec![file("1.mkv", "jpn"), file("2.mkv", "eng")];
+        let groups = groupby(&files, key_audlang_audname);
+        let id = groups[1].id();
+        assert_eq!(
+            find_group(&groups, &id).unwrap().files[0].file_name,
+            "1.mkv"
+        );
+        // Numbers of the listing are not ids
+        assert!(find_group(&groups, "0").is_err());
+        assert!(find_group(&groups, "000000000000000a").is_err());
+        let same_id = vec![groups[0].clone(), groups[0].clone()];
+        assert!(find_group(&same_id, &groups[0].id()).is_err());
+    }
+}
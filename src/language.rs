@@ -0,0 +1,65 @@
+/// ISO 639-2 codes (as used by Matroska) with their english names.
+const LANGUAGES: [(&str, &str); 45] = [
+    ("ara", "Arabic"),
+    ("bul", "Bulgarian"),
+    ("cat", "Catalan"),
+    ("ces", "Czech"),
+    ("chi", "Chinese"),
+    ("cze", "Czech"),
+    ("dan", "Danish"),
+    ("deu", "German"),
+    ("dut", "Dutch"),
+    ("ell", "Greek"),
+    ("eng", "English"),
+    ("est", "Estonian"),
+    ("fin", "Finnish"),
+    ("fra", "French"),
+    ("fre", "French"),
+    ("ger", "German"),
+    ("gre", "Greek"),
+    ("heb", "Hebrew"),
+    ("hin", "Hindi"),
+    ("hrv", "Croatian"),
+    ("hun", "Hungarian"),
+    ("ice", "Icelandic"),
+    ("ind", "Indonesian"),
+    ("isl", "Icelandic"),
+    ("ita", "Italian"),
+    ("jpn", "Japanese"),
+    ("kor", "Korean"),
+    ("lav", "Latvian"),
+    ("lit", "Lithuanian"),
+    ("may", "Malay"),
+    ("nld", "Dutch"),
+    ("nor", "Norwegian"),
+    ("pol", "Polish"),
+    ("por", "Portuguese"),
+    ("ron", "Romanian"),
+    ("rum", "Romanian"),
+    ("rus", "Russian"),
+    ("slk", "Slovak"),
+    ("slv", "Slovenian"),
+    ("spa", "Spanish"),
+    ("swe", "Swedish"),
+    ("tha", "Thai"),
+    ("tur", "Turkish"),
+    ("ukr", "Ukrainian"),
+    ("vie", "Vietnamese"),
+];
+
+/// English name of a language code, e.g. "German" for "ger" or "deu".
+pub fn language_name(code: &str) -> Option<&'static str> {
+    LANGUAGES
+        .iter()
+        .find(|(lang_code, _)| *lang_code == code)
+        .map(|(_, name)| *name)
+}
+
+/// Whether the text only names the language, e.g. "eng", "English" or "ENGLISH".
+pub fn is_language_label(text: &str, code: &str) -> bool {
+    let text = text.trim();
+    text.eq_ignore_ascii_case(code)
+        || language_name(code)
+            .map(|name| text.eq_ignore_ascii_case(name))
+            .unwrap_or(false)
+}
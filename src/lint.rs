@@ -0,0 +1,545 @@
+use crate::analyze::name_indicates_forced;
+use crate::command::Command;
+use crate::file::{File, Track, TrackType};
+use crate::language::is_language_label;
+use crate::policy::DefaultPolicy;
+use crate::scan::ScanError;
+use crate::track_operations::{EditPlan, TrackOperation};
+
+use anyhow::{Result, anyhow};
+use serde_json::json;
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Severity {
+    Off,
+    Info,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    pub fn from_id(id: &str) -> Option<Self> {
+        match id {
+            "off" => Some(Severity::Off),
+            "info" => Some(Severity::Info),
+            "warning" => Some(Severity::Warning),
+            "error" => Some(Severity::Error),
+            _ => None,
+        }
+    }
+
+    pub fn id(&self) -> &'static str {
+        match self {
+            Severity::Off => "off",
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Rule {
+    NoDefaultAudio,
+    MultipleDefaultAudio,
+    MultipleDefaultSubs,
+    UndLanguage,
+    ForcedFullSubs,
+    DisabledTrack,
+    EmptyName,
+    NameIsLanguage,
+}
+
+impl Rule {
+    pub const ALL: [Rule; 8] = [
+        Rule::NoDefaultAudio,
+        Rule::MultipleDefaultAudio,
+        Rule::MultipleDefaultSubs,
+        Rule::UndLanguage,
+        Rule::ForcedFullSubs,
+        Rule::DisabledTrack,
+        Rule::EmptyName,
+        Rule::NameIsLanguage,
+    ];
+
+    pub fn id(&self) -> &'static str {
+        match self {
+            Rule::NoDefaultAudio => "no-default-audio",
+            Rule::MultipleDefaultAudio => "multiple-default-audio",
+            Rule::MultipleDefaultSubs => "multiple-default-subs",
+            Rule::UndLanguage => "und-language",
+            Rule::ForcedFullSubs => "forced-full-subs",
+            Rule::DisabledTrack => "disabled-track",
+            Rule::EmptyName => "empty-name",
+            Rule::NameIsLanguage => "name-is-language",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        Rule::ALL.into_iter().find(|rule| rule.id() == id)
+    }
+
+    /// Track type the rule is about, None for rules that check all track types.
+    pub fn track_type(&self) -> Option<TrackType> {
+        match self {
+            Rule::NoDefaultAudio | Rule::MultipleDefaultAudio => Some(TrackType::Audio),
+            Rule::MultipleDefaultSubs | Rule::ForcedFullSubs => Some(TrackType::Subtitles),
+            _ => None,
+        }
+    }
+
+    pub fn default_severity(&self) -> Severity {
+        match self {
+            Rule::NoDefaultAudio => Severity::Error,
+            Rule::MultipleDefaultAudio => Severity::Warning,
+            Rule::MultipleDefaultSubs => Severity::Warning,
+            Rule::UndLanguage => Severity::Warning,
+            Rule::ForcedFullSubs => Severity::Warning,
+            Rule::DisabledTrack => Severity::Info,
+            Rule::EmptyName => Severity::Info,
+            Rule::NameIsLanguage => Severity::Info,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Finding {
+    pub rule: Rule,
+    pub severity: Severity,
+    pub file_name: String,
+    pub track_type: Option<TrackType>,
+    /// Index of the track within the tracks of its type.
+    pub track_no: Option<usize>,
+    pub message: String,
+    pub fix: Option<Fix>,
+}
+
+/// Operations that resolve a finding.
+#[derive(Clone)]
+pub struct Fix {
+    pub description: String,
+    pub track_type: TrackType,
+    pub operations: Vec<(i64, TrackOperation<'static>)>,
+}
+
+impl std::fmt::Debug for Fix {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Fix({})", self.description)
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct LintConfig {
+    severities: HashMap<Rule, Severity>,
+    /// Used to choose the new default track when fixing default flags.
+    pub policy: DefaultPolicy,
+}
+
+impl LintConfig {
+    pub fn severity(&self, rule: Rule) -> Severity {
+        self.severities
+            .get(&rule)
+            .copied()
+            .unwrap_or_else(|| rule.default_severity())
+    }
+
+    /// Overrides the severity of a rule, given as "rule=severity".
+    pub fn set_from_str(&mut self, setting: &str) -> Result<()> {
+        let (rule_id, severity_id) = setting
+            .split_once('=')
+            .ok_or_else(|| anyhow!("Expected rule=severity, got {}", setting))?;
+        let rule = Rule::from_id(rule_id.trim())
+            .ok_or_else(|| anyhow!("Unknown lint rule {}", rule_id))?;
+        let severity = Severity::from_id(severity_id.trim())
+            .ok_or_else(|| anyhow!("Unknown severity {}", severity_id))?;
+        self.severities.insert(rule, severity);
+        Ok(())
+    }
+}
+
+fn track_type_name(track_type: TrackType) -> &'static str {
+    match track_type {
+        TrackType::Video => "video",
+        TrackType::Audio => "audio",
+        TrackType::Subtitles => "subtitle",
+    }
+}
+
+struct FileLinter<'a> {
+    file: &'a File,
+    config: &'a LintConfig,
+    findings: Vec<Finding>,
+}
+
+impl FileLinter<'_> {
+    fn report(
+        &mut self,
+        rule: Rule,
+        track: Option<(TrackType, usize)>,
+        message: impl Into<String>,
+        fix: Option<Fix>,
+    ) {
+        let severity = self.config.severity(rule);
+        if severity == Severity::Off {
+            return;
+        }
+        self.findings.push(Finding {
+            rule,
+            severity,
+            file_name: self.file.file_name.clone(),
+            track_type: track.map(|(track_type, _)| track_type),
+            track_no: track.map(|(_, track_no)| track_no),
+            message: message.into(),
+            fix,
+        });
+    }
+
+    fn exclusive_default_fix(&self, track_type: TrackType, track_no: usize) -> Fix {
+        Fix {
+            description: format!("make track {} the only default track", track_no),
+            track_type,
+            operations: vec![(track_no as i64, TrackOperation::SetDefaultExclusive(true))],
+        }
+    }
+
+    fn check_defaults(&mut self) {
+        let audio_defaults = self.file.audio_tracks.iter().filter(|t| t.default).count();
+        let audio_choice = self
+            .config
+            .policy
+            .choose(&self.file.audio_tracks)
+            .map(|chosen| self.exclusive_default_fix(TrackType::Audio, chosen));
+        if !self.file.audio_tracks.is_empty() && audio_defaults == 0 {
+            self.report(
+                Rule::NoDefaultAudio,
+                None,
+                "no audio track is marked as default",
+                audio_choice.clone(),
+            );
+        }
+        if audio_defaults > 1 {
+            self.report(
+                Rule::MultipleDefaultAudio,
+                None,
+                format!("{} audio tracks are marked as default", audio_defaults),
+                audio_choice,
+            );
+        }
+        let sub_defaults = self
+            .file
+            .subtitle_tracks
+            .iter()
+            .filter(|t| t.default)
+            .count();
+        if sub_defaults > 1 {
+            // Keep the first default subtitle track
+            let fix = self
+                .file
+                .subtitle_tracks
+                .iter()
+                .position(|t| t.default)
+                .map(|first| self.exclusive_default_fix(TrackType::Subtitles, first));
+            self.report(
+                Rule::MultipleDefaultSubs,
+                None,
+                format!("{} subtitle tracks are marked as default", sub_defaults),
+                fix,
+            );
+        }
+    }
+
+    fn check_track(&mut self, track_type: TrackType, track_no: usize, track: &Track) {
+        let location = Some((track_type, track_no));
+        let fix = |description: &str, operation| {
+            Some(Fix {
+                description: description.to_owned(),
+                track_type,
+                operations: vec![(track_no as i64, operation)],
+            })
+        };
+        if !track.enabled {
+            self.report(
+                Rule::DisabledTrack,
+                location,
+                "track is disabled",
+                fix("enable the track", TrackOperation::SetEnabled(true)),
+            );
+        }
+        if matches!(track_type, TrackType::Video) {
+            return;
+        }
+        match track.language {
+            None => self.report(
+                Rule::UndLanguage,
+                location,
+                "language is undetermined",
+                None,
+            ),
+            Some(ref language) => {
+                if let Some(ref name) = track.name
+                    && is_language_label(name, language)
+                {
+                    self.report(
+                        Rule::NameIsLanguage,
+                        location,
+                        format!("name \"{}\" only repeats the language", name),
+                        fix("remove the name", TrackOperation::SetTitle(None)),
+                    );
+                }
+            }
+        }
+        if track.name.is_none() {
+            self.report(Rule::EmptyName, location, "track has no name", None);
+        }
+        if matches!(track_type, TrackType::Subtitles) && track.forced && self.is_full_subs(track) {
+            self.report(
+                Rule::ForcedFullSubs,
+                location,
+                "forced flag is set on a full subtitle track",
+                fix("unset the forced flag", TrackOperation::SetForced(false)),
+            );
+        }
+    }
+
+    /// A subtitle track is considered full if its name does not indicate forced subtitles
+    /// and it is the only subtitle track of its language.
+    fn is_full_subs(&self, track: &Track) -> bool {
+        if track.name.as_deref().is_some_and(name_indicates_forced) {
+            return false;
+        }
+        self.file
+            .subtitle_tracks
+            .iter()
+            .filter(|other| other.language == track.language)
+            .count()
+            == 1
+    }
+}
+
+pub fn lint_file(file: &File, config: &LintConfig) -> Vec<Finding> {
+    let mut linter = FileLinter {
+        file,
+        config,
+        findings: Vec::new(),
+    };
+    linter.check_defaults();
+    for track_type in [TrackType::Video, TrackType::Audio, TrackType::Subtitles] {
+        for (track_no, track) in file.tracks(track_type).iter().enumerate() {
+            linter.check_track(track_type, track_no, track);
+        }
+    }
+    linter.findings
+}
+
+pub fn lint_files(files: &[File], config: &LintConfig) -> Vec<Finding> {
+    files
+        .iter()
+        .flat_map(|file| lint_file(file, config))
+        .collect()
+}
+
+impl Finding {
+    pub fn location(&self) -> String {
+        match (self.track_type, self.track_no) {
+            (Some(track_type), Some(track_no)) => {
+                format!("{} track {}", track_type_name(track_type), track_no)
+            }
+            _ => "file".to_owned(),
+        }
+    }
+}
+
+/// Commands that apply the fixes of the findings, one command per file.
+pub fn fix_commands<'f>(
+    findings: impl IntoIterator<Item = &'f Finding>,
+    files: &[File],
+) -> Vec<Command> {
+    let mut commands = Vec::new();
+    let mut findings: Vec<&Finding> = findings.into_iter().filter(|f| f.fix.is_some()).collect();
+    // Stable sort keeps the order of the fixes within a file
+    findings.sort_by(|a, b| a.file_name.cmp(&b.file_name));
+    for file_findings in findings.chunk_by(|a, b| a.file_name == b.file_name) {
+        let Some(file) = files
+            .iter()
+            .find(|file| file.file_name == file_findings[0].file_name)
+        else {
+            continue;
+        };
+        let mut plan = EditPlan::new();
+        let fixes = file_findings
+            .iter()
+            .filter_map(|finding| finding.fix.as_ref());
+        for fix in fixes {
+            for (track_no, operation) in &fix.operations {
+                plan.add(fix.track_type, *track_no, *operation);
+            }
+        }
+        if !plan.empty() {
+            commands.push(plan.generate_command(file));
+        }
+    }
+    commands
+}
+
+/// Files that could not be scanned or identified are listed as errors after the findings.
+pub fn text_report(findings: &[Finding], scan_errors: &[ScanError]) -> String {
+    let mut report = String::new();
+    let mut last_file: Option<&str> = None;
+    for finding in findings {
+        if last_file != Some(finding.file_name.as_str()) {
+            report.push_str(&finding.file_name);
+            report.push('\n');
+            last_file = Some(finding.file_name.as_str());
+        }
+        report.push_str(&format!(
+            "    {:<8}[{}] {}: {}\n",
+            finding.severity.id(),
+            finding.rule.id(),
+            finding.location(),
+            finding.message
+        ));
+        if let Some(ref fix) = finding.fix {
+            report.push_str(&format!("            fix: {}\n", fix.description));
+        }
+    }
+    for scan_error in scan_errors {
+        report.push_str(&format!("Could not read {}\n", scan_error));
+    }
+    let count = |severity| findings.iter().filter(|f| f.severity == severity).count();
+    report.push_str(&format!(
+        "{} errors, {} warnings, {} infos",
+        count(Severity::Error),
+        count(Severity::Warning),
+        count(Severity::Info)
+    ));
+    if !scan_errors.is_empty() {
+        report.push_str(&format!(", {} files could not be read", scan_errors.len()));
+    }
+    report.push('\n');
+    report
+}
+
+/// Files that could not be scanned or identified are reported with the rule "unreadable".
+pub fn json_report(findings: &[Finding], scan_errors: &[ScanError]) -> String {
+    let mut findings: Vec<_> = findings
+        .iter()
+        .map(|finding| {
+            json!({
+                "file": finding.file_name,
+                "rule": finding.rule.id(),
+                "severity": finding.severity.id(),
+                "track_type": finding.track_type.map(track_type_name),
+                "track": finding.track_no,
+                "message": finding.message,
+                "fix": finding.fix.as_ref().map(|fix| fix.description.clone()),
+            })
+        })
+        .collect();
+    findings.extend(scan_errors.iter().map(|scan_error| {
+        json!({
+            "file": scan_error.path.as_ref().map(|path| path.to_string_lossy()),
+            "rule": "unreadable",
+            "severity": Severity::Error.id(),
+            "track_type": null,
+            "track": null,
+            "message": scan_error.message,
+            "fix": null,
+        })
+    }));
+    serde_json::to_string_pretty(&findings).expect("findings can be serialized")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_file() -> File {
+        File::from_json_str(
+            r#"{
+                "file_name": "episode.mkv",
+                "tracks": [
+                    {"id": 0, "type": "audio", "properties": {"language": "jpn",
+                        "default_track": false, "forced_track": false, "enabled_track": true,
+                        "track_name": "Japanese"}},
+                    {"id": 1, "type": "subtitles", "properties": {"language": "eng",
+                        "default_track": true, "forced_track": true, "enabled_track": true,
+                        "track_name": "Full"}},
+                    {"id": 2, "type": "subtitles", "properties": {"language": "und",
+                        "default_track": true, "forced_track": false, "enabled_track": false}}
+                ]
+            }"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_lint_file() {
+        let mut config = LintConfig::default();
+        config.set_from_str("empty-name=off").unwrap();
+        let findings = lint_file(&test_file(), &config);
+        let rules: Vec<(Rule, Option<usize>)> =
+            findings.iter().map(|f| (f.rule, f.track_no)).collect();
+        assert_eq!(
+            rules,
+            vec![
+                (Rule::NoDefaultAudio, None),
+                (Rule::MultipleDefaultSubs, None),
+                (Rule::NameIsLanguage, Some(0)),
+                (Rule::ForcedFullSubs, Some(0)),
+                (Rule::DisabledTrack, Some(1)),
+                (Rule::UndLanguage, Some(1)),
+            ]
+        );
+        assert!(config.set_from_str("unknown=error").is_err());
+    }
+
+    #[test]
+    fn test_report_scan_errors() {
+        let scan_errors = vec![ScanError {
+            path: Some("broken.mkv".into()),
+            message: "not a Matroska file".to_owned(),
+        }];
+        let report = text_report(&[], &scan_errors);
+        assert_eq!(
+            report,
+            "Could not read broken.mkv: not a Matroska file\n\
+             0 errors, 0 warnings, 0 infos, 1 files could not be read\n"
+        );
+        let report: serde_json::Value =
+            serde_json::from_str(&json_report(&[], &scan_errors)).unwrap();
+        assert_eq!(report[0]["file"], "broken.mkv");
+        assert_eq!(report[0]["rule"], "unreadable");
+        assert_eq!(report[0]["severity"], "error");
+    }
+
+    #[test]
+    fn test_fix_commands() {
+        let file = test_file();
+        let config = LintConfig::default();
+        let findings = lint_file(&file, &config);
+        let fixes = findings
+            .iter()
+            .filter(|f| matches!(f.rule, Rule::NameIsLanguage | Rule::DisabledTrack));
+        let commands = fix_commands(fixes, std::slice::from_ref(&file));
+        // Both fixes are applied by a single command for the file
+        assert_eq!(commands.len(), 1);
+        assert_eq!(
+            commands[0].arguments,
+            vec![
+                "--edit",
+                "track:@1",
+                "--delete",
+                "name",
+                "--edit",
+                "track:@3",
+                "--set",
+                "flag-enabled=1",
+                "episode.mkv",
+            ]
+        );
+        // Findings without a fix do not produce a command
+        let und = findings.iter().filter(|f| f.rule == Rule::UndLanguage);
+        assert!(fix_commands(und, std::slice::from_ref(&file)).is_empty());
+    }
+}
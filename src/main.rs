@@ -1,38 +1,68 @@
+mod analyze;
 mod command;
 mod file;
 mod group;
+mod language;
+mod lint;
+mod naming;
+mod policy;
+mod scan;
+mod stats;
 mod table;
 mod track_operations;
 mod ui;
+mod watch;
 
+use crate::analyze::suggest_for_file;
 use crate::file::{File, TrackType};
-use crate::group::{groupby, key_audlang_audname, key_sublang_subname, print_groups};
-use crate::track_operations::{TrackOperation, TrackOperations};
-use crate::ui::main_loop;
+use crate::group::{
+    FileSort, Group, GroupKey, GroupSort, find_group, groupby, key_audlang_audname,
+    key_sublang_subname, print_groups, sort_groups,
+};
+use crate::lint::{LintConfig, Severity, fix_commands, json_report, lint_files, text_report};
+use crate::naming::{NameReplace, NameRule, NameTemplate, RenameScope, rename_commands};
+use crate::policy::{DefaultPolicy, LOSSLESS_CODECS, PolicyRules};
+use crate::track_operations::{EditPlan, FileOperation, SegmentOperation, TrackOperation};
+use crate::ui::{Keymap, THEMES, Theme, config_file, main_loop};
 
+use crate::scan::{
+    DEFAULT_EXTENSIONS, ScanError, ScanOptions, build_globset, collect_files, read_file_list,
+};
+use regex::Regex;
+
+use crate::watch::watch;
+use std::collections::HashSet;
 use std::path::{Path, PathBuf};
-use walkdir::WalkDir;
-fn get_files_recursively(path: &Path) -> Vec<PathBuf> {
-    let walker = WalkDir::new(path).into_iter();
-    let files: Vec<PathBuf> = walker
-        .filter(|e| e.as_ref().unwrap().metadata().unwrap().is_file())
-        .map(|e| e.unwrap().path().to_path_buf())
-        .filter(|e| e.extension().map(|e| e == "mkv").unwrap_or(false))
-        .collect();
-    files
-}
+use std::time::Duration;
 
+use anyhow::Context;
 use clap::{Arg, ArgAction, Command};
 
 fn main() -> anyhow::Result<()> {
-    let arg_directory = Arg::new("directory")
-        .help("Path to directory")
-        .required(true);
+    let arg_paths = Arg::new("paths")
+        .help("Directories to scan and files to load")
+        .value_name("PATH")
+        .num_args(1..)
+        .required_unless_present("files-from");
+    let arg_files_from = Arg::new("files-from")
+        .help("Read a NUL-separated list of files from FILE, use - for stdin")
+        .required(false)
+        .value_name("FILE")
+        .long("files-from")
+        .action(ArgAction::Set);
     let arg_group = Arg::new("group")
-        .help("Path to directory")
+        .help("Id of the group as printed in the listing")
         .required(false)
-        .value_name("GROUP")
+        .value_name("ID")
         .long("group")
+        .conflicts_with("group-no")
+        .action(ArgAction::Set);
+    let arg_group_no = Arg::new("group-no")
+        .help("Number of the group in the listing, depends on --sort-groups")
+        .required(false)
+        .value_name("NUMBER")
+        .long("group-no")
+        .value_parser(clap::value_parser!(usize))
         .action(ArgAction::Set);
     let arg_track = Arg::new("track")
         .help("Track number of the selected group")
@@ -51,6 +81,7 @@ fn main() -> anyhow::Result<()> {
         .required(false)
         .value_name("TRACK")
         .long("set-default-ex")
+        .conflicts_with("set-default")
         .action(ArgAction::Set);
     let arg_forced = Arg::new("set-forced")
         .help("Set the track with the specified number as forced")
@@ -64,38 +95,250 @@ fn main() -> anyhow::Result<()> {
         .value_name("TRACK")
         .long("set-enabled")
         .action(ArgAction::Set);
+    let arg_rename = Arg::new("rename")
+        .help("Set the track names from a template, e.g. '{lang_name} {channels} {codec}{forced?\" (Forced)\"}'. Applies to --track or all tracks of the group")
+        .required(false)
+        .value_name("TEMPLATE")
+        .long("rename")
+        .action(ArgAction::Set);
+    let arg_template = Arg::new("template")
+        .help("Set the track names from a template, see --rename of the subs command")
+        .required_unless_present("find")
+        .conflicts_with("find")
+        .value_name("TEMPLATE")
+        .long("template")
+        .action(ArgAction::Set);
+    let arg_find = Arg::new("find")
+        .help("Regular expression to search for in the track names")
+        .required(false)
+        .requires("replace")
+        .value_name("REGEX")
+        .long("find")
+        .action(ArgAction::Set);
+    let arg_replace = Arg::new("replace")
+        .help("Replacement for the matches, may refer to capture groups like $1")
+        .required(false)
+        .requires("find")
+        .value_name("TEXT")
+        .long("replace")
+        .action(ArgAction::Set);
+    let arg_language = Arg::new("language")
+        .help("Only rename tracks with this language")
+        .required(false)
+        .value_name("LANG")
+        .long("language")
+        .action(ArgAction::Set);
+    let arg_segment_title = Arg::new("set-segment-title")
+        .help("Set the title of the files, an empty title removes it")
+        .required(false)
+        .value_name("TITLE")
+        .long("set-segment-title")
+        .action(ArgAction::Set);
+    let arg_add_stats = Arg::new("add-statistics-tags")
+        .help("Regenerate the track statistics tags")
+        .required(false)
+        .long("add-statistics-tags")
+        .conflicts_with("delete-statistics-tags")
+        .action(ArgAction::SetTrue);
+    let arg_delete_stats = Arg::new("delete-statistics-tags")
+        .help("Delete the track statistics tags")
+        .required(false)
+        .long("delete-statistics-tags")
+        .action(ArgAction::SetTrue);
+    let arg_set_global_tags = Arg::new("set-global-tags")
+        .help("Replace the global tags with the tags from the specified XML file")
+        .required(false)
+        .value_name("FILE")
+        .long("set-global-tags")
+        .conflicts_with("clear-global-tags")
+        .action(ArgAction::Set);
+    let arg_clear_global_tags = Arg::new("clear-global-tags")
+        .help("Remove all global tags")
+        .required(false)
+        .long("clear-global-tags")
+        .action(ArgAction::SetTrue);
+    let arg_track_type = Arg::new("type")
+        .help("Type of the tracks to edit")
+        .required(false)
+        .value_name("TYPE")
+        .long("type")
+        .value_parser(["audio", "subs"])
+        .default_value("audio")
+        .action(ArgAction::Set);
+    let arg_dry_run = Arg::new("dry-run")
+        .help("Only print the commands, do not apply the changes")
+        .required(false)
+        .long("dry-run")
+        .action(ArgAction::SetTrue);
+    let arg_count_events = Arg::new("count-events")
+        .help("Extract text subtitles to detect forced subtitles by their number of events")
+        .required(false)
+        .long("count-events")
+        .action(ArgAction::SetTrue);
+    let arg_apply = Arg::new("apply")
+        .help("Apply the suggested changes")
+        .required(false)
+        .long("apply")
+        .action(ArgAction::SetTrue);
+    let arg_directory = Arg::new("directory")
+        .help("Path to directory")
+        .required(true);
+    let arg_policy = Arg::new("policy")
+        .help("TOML file with the rules to apply")
+        .required(true)
+        .value_name("FILE")
+        .long("policy")
+        .action(ArgAction::Set);
+    let arg_settle = Arg::new("settle")
+        .help("Seconds without writes before a file is considered complete")
+        .required(false)
+        .value_name("SECONDS")
+        .long("settle")
+        .value_parser(clap::value_parser!(u64))
+        .default_value("10")
+        .action(ArgAction::Set);
+    let arg_format = Arg::new("format")
+        .help("Output format of the report")
+        .required(false)
+        .value_name("FORMAT")
+        .long("format")
+        .value_parser(["text", "json"])
+        .default_value("text")
+        .action(ArgAction::Set);
+    let arg_severity = Arg::new("severity")
+        .help("Override the severity of a rule, e.g. empty-name=off (repeatable)")
+        .required(false)
+        .value_name("RULE=SEVERITY")
+        .long("severity")
+        .action(ArgAction::Append);
+    let arg_fail_on = Arg::new("fail-on")
+        .help("Exit with a non-zero status if there are findings of this severity or higher")
+        .required(false)
+        .value_name("SEVERITY")
+        .long("fail-on")
+        .value_parser(["info", "warning", "error"])
+        .default_value("warning")
+        .action(ArgAction::Set);
+    let arg_fix = Arg::new("fix")
+        .help("Apply the automatic fixes of the findings")
+        .required(false)
+        .long("fix")
+        .action(ArgAction::SetTrue);
+    let arg_keymap = Arg::new("keymap")
+        .help("Keymap file, defaults to ~/.config/mtxstuff/keymap.toml if it exists")
+        .required(false)
+        .value_name("FILE")
+        .long("keymap")
+        .action(ArgAction::Set);
+    let arg_theme = Arg::new("theme")
+        .help(format!(
+            "Color theme ({}) or theme file, defaults to ~/.config/mtxstuff/theme.toml if it exists",
+            THEMES.join(", ")
+        ))
+        .required(false)
+        .value_name("THEME")
+        .long("theme")
+        .action(ArgAction::Set);
     let matches = Command::new("mtxstuff")
         .version(env!("CARGO_PKG_VERSION"))
         .author("Moritz Schulte")
         .about("mtxstuff")
         .propagate_version(true)
-        .subcommand(
+        .subcommand(policy_args(sort_args(scan_args(
             Command::new("subs")
                 .about("controls testing features")
-                .arg(&arg_directory)
+                .arg(&arg_paths)
+                .arg(&arg_files_from)
                 .arg(&arg_group)
+                .arg(&arg_group_no)
                 .arg(&arg_track)
                 .arg(&arg_forced)
                 .arg(&arg_enabled)
                 .arg(&arg_default_ex)
-                .arg(&arg_default),
-        )
-        .subcommand(
+                .arg(&arg_default)
+                .arg(&arg_rename)
+                .arg(&arg_segment_title)
+                .arg(&arg_dry_run)
+                .arg(&arg_add_stats)
+                .arg(&arg_delete_stats)
+                .arg(&arg_set_global_tags)
+                .arg(&arg_clear_global_tags),
+        ))))
+        .subcommand(policy_args(sort_args(scan_args(
             Command::new("audio")
                 .about("controls testing features")
-                .arg(&arg_directory)
+                .arg(&arg_paths)
+                .arg(&arg_files_from)
                 .arg(&arg_group)
+                .arg(&arg_group_no)
                 .arg(&arg_track)
                 .arg(&arg_forced)
                 .arg(&arg_enabled)
                 .arg(&arg_default_ex)
-                .arg(&arg_default),
-        )
-        .subcommand(
+                .arg(&arg_default)
+                .arg(&arg_rename)
+                .arg(&arg_segment_title)
+                .arg(&arg_dry_run)
+                .arg(&arg_add_stats)
+                .arg(&arg_delete_stats)
+                .arg(&arg_set_global_tags)
+                .arg(&arg_clear_global_tags),
+        ))))
+        .subcommand(policy_args(sort_args(scan_args(
             Command::new("tui")
                 .about("controls testing features")
-                .arg(&arg_directory),
-        )
+                .arg(&arg_paths)
+                .arg(&arg_files_from)
+                .arg(&arg_keymap)
+                .arg(&arg_theme),
+        ))))
+        .subcommand(policy_args(scan_args(
+            Command::new("choose-default")
+                .about("Set the best track of every file as exclusive default track")
+                .arg(&arg_paths)
+                .arg(&arg_files_from)
+                .arg(&arg_track_type)
+                .arg(&arg_dry_run),
+        )))
+        .subcommand(scan_args(
+            Command::new("analyze")
+                .about("Suggest forced, hearing impaired and commentary flags from track names")
+                .arg(&arg_paths)
+                .arg(&arg_files_from)
+                .arg(&arg_track_type)
+                .arg(&arg_count_events)
+                .arg(&arg_apply),
+        ))
+        .subcommand(policy_args(scan_args(
+            Command::new("lint")
+                .about("Check all files for inconsistent track flags and metadata")
+                .arg(&arg_paths)
+                .arg(&arg_files_from)
+                .arg(&arg_format)
+                .arg(&arg_severity)
+                .arg(&arg_fail_on)
+                .arg(&arg_fix)
+                .arg(&arg_dry_run),
+        )))
+        .subcommand(scan_args(
+            Command::new("rename")
+                .about("Rename tracks of all files with a template or find and replace")
+                .arg(&arg_paths)
+                .arg(&arg_files_from)
+                .arg(&arg_track_type)
+                .arg(&arg_language)
+                .arg(&arg_template)
+                .arg(&arg_find)
+                .arg(&arg_replace)
+                .arg(&arg_apply),
+        ))
+        .subcommand(scan_args(
+            Command::new("watch")
+                .about("Apply a policy to new files as soon as they are completely written")
+                .arg(&arg_directory)
+                .arg(&arg_policy)
+                .arg(&arg_settle),
+        ))
         .get_matches();
 
     let (sub_name, sub_matches) = match matches.subcommand() {
@@ -116,30 +359,264 @@ fn main() -> anyhow::Result<()> {
         return Ok(());
     }
 
-    let path = sub_matches.get_one::<String>("directory");
-    let path = PathBuf::from(path.unwrap());
-    let paths = get_files_recursively(&path);
-    let files: anyhow::Result<Vec<File>> = paths
-        .iter()
-        .map(AsRef::as_ref)
-        .map(File::from_path)
-        .collect();
-    let files = files?;
+    if sub_name == "watch" {
+        let directory = PathBuf::from(sub_matches.get_one::<String>("directory").unwrap());
+        let rules =
+            PolicyRules::from_path(Path::new(sub_matches.get_one::<String>("policy").unwrap()))?;
+        let settle = Duration::from_secs(*sub_matches.get_one::<u64>("settle").unwrap());
+        let scan_options = scan_options_from_matches(sub_matches)?;
+        return watch(&directory, &rules, &scan_options, settle);
+    }
+
+    let mut paths: Vec<PathBuf> = sub_matches
+        .get_many::<String>("paths")
+        .map(|paths| paths.map(PathBuf::from).collect())
+        .unwrap_or_default();
+    if let Some(files_from) = sub_matches.get_one::<String>("files-from") {
+        let file_list = if files_from == "-" {
+            read_file_list(std::io::stdin().lock())
+        } else {
+            read_file_list(std::fs::File::open(files_from)?)
+        };
+        paths.extend(file_list?);
+    }
+    let scan_options = scan_options_from_matches(sub_matches)?;
+    let scan_result = collect_files(&paths, &scan_options);
+    let mut scan_errors = scan_result.errors;
+    let mut files: Vec<File> = Vec::new();
+    for file_path in scan_result.files.iter() {
+        match File::from_path(file_path) {
+            Ok(file) => files.push(file),
+            Err(err) => scan_errors.push(ScanError {
+                path: Some(file_path.clone()),
+                message: format!("{:#}", err),
+            }),
+        }
+    }
+    for scan_error in &scan_errors {
+        eprintln!("Skipping {}", scan_error);
+    }
 
     match sub_name {
-        "subs" => cli_mode(files, sub_name, sub_matches),
-        "audio" => cli_mode(files, sub_name, sub_matches),
-        "video" => cli_mode(files, sub_name, sub_matches),
-        "tui" => tui_mode(files), //, sub_name, sub_matches),
+        "subs" => cli_mode(files, sub_name, sub_matches)?,
+        "audio" => cli_mode(files, sub_name, sub_matches)?,
+        "video" => cli_mode(files, sub_name, sub_matches)?,
+        "tui" => tui_mode(files, sub_matches, scan_errors.len())?,
+        "choose-default" => choose_default_mode(files, sub_matches)?,
+        "analyze" => analyze_mode(files, sub_matches)?,
+        "lint" => lint_mode(files, &scan_errors, sub_matches)?,
+        "rename" => rename_mode(files, sub_matches)?,
         _ => panic!(),
     }
     Ok(())
 }
 
-fn cli_mode(files: Vec<File>, sub_name: &str, sub_matches: &clap::ArgMatches) {
-    let group_no = sub_matches
-        .get_one::<String>("group")
-        .and_then(|o| o.parse::<usize>().ok());
+/// Arguments that select which files are scanned.
+fn scan_args(command: Command) -> Command {
+    let arg_extensions = Arg::new("extensions")
+        .help(format!(
+            "Comma separated list of file extensions to scan, case-insensitive [default: {}]",
+            DEFAULT_EXTENSIONS.join(",")
+        ))
+        .required(false)
+        .value_name("EXTS")
+        .long("extensions")
+        .value_delimiter(',')
+        .action(ArgAction::Set);
+    let arg_include = Arg::new("include")
+        .help("Only scan files whose path relative to the directory matches the glob (repeatable)")
+        .required(false)
+        .value_name("GLOB")
+        .long("include")
+        .action(ArgAction::Append);
+    let arg_exclude = Arg::new("exclude")
+        .help("Skip files whose path relative to the directory matches the glob (repeatable)")
+        .required(false)
+        .value_name("GLOB")
+        .long("exclude")
+        .action(ArgAction::Append);
+    let arg_regex = Arg::new("regex")
+        .help("Only scan files whose path matches the regular expression")
+        .required(false)
+        .value_name("REGEX")
+        .long("regex")
+        .action(ArgAction::Set);
+    let arg_exclude_regex = Arg::new("exclude-regex")
+        .help("Skip files whose path matches the regular expression")
+        .required(false)
+        .value_name("REGEX")
+        .long("exclude-regex")
+        .action(ArgAction::Set);
+    let arg_max_depth = Arg::new("max-depth")
+        .help("Maximum directory depth to descend into")
+        .required(false)
+        .value_name("DEPTH")
+        .long("max-depth")
+        .value_parser(clap::value_parser!(usize))
+        .action(ArgAction::Set);
+    let arg_follow_symlinks = Arg::new("follow-symlinks")
+        .help("Follow symbolic links")
+        .required(false)
+        .long("follow-symlinks")
+        .action(ArgAction::SetTrue);
+    command
+        .arg(arg_extensions)
+        .arg(arg_include)
+        .arg(arg_exclude)
+        .arg(arg_regex)
+        .arg(arg_exclude_regex)
+        .arg(arg_max_depth)
+        .arg(arg_follow_symlinks)
+}
+
+/// Arguments for the order of groups and of the files within a group.
+fn sort_args(command: Command) -> Command {
+    let arg_sort_groups = Arg::new("sort-groups")
+        .help("Order of the groups, group numbers refer to this order")
+        .required(false)
+        .value_name("ORDER")
+        .long("sort-groups")
+        .value_parser(GroupSort::ALL.map(|sort| sort.id()))
+        .default_value(GroupSort::default().id())
+        .action(ArgAction::Set);
+    let arg_sort_files = Arg::new("sort-files")
+        .help("Order of the files within a group")
+        .required(false)
+        .value_name("ORDER")
+        .long("sort-files")
+        .value_parser(FileSort::ALL.map(|sort| sort.id()))
+        .default_value(FileSort::default().id())
+        .action(ArgAction::Set);
+    command.arg(arg_sort_groups).arg(arg_sort_files)
+}
+
+/// Arguments of the policy that chooses the default track.
+fn policy_args(command: Command) -> Command {
+    let arg_languages = Arg::new("languages")
+        .help("Comma separated list of preferred languages, best first (e.g. jpn,eng)")
+        .required(false)
+        .value_name("LANGS")
+        .long("languages")
+        .value_delimiter(',')
+        .action(ArgAction::Set);
+    let arg_codecs = Arg::new("codecs")
+        .help("Comma separated list of preferred codecs, best first (e.g. TrueHD,DTS,AC-3)")
+        .required(false)
+        .value_name("CODECS")
+        .long("codecs")
+        .value_delimiter(',')
+        .action(ArgAction::Set);
+    let arg_prefer_lossless = Arg::new("prefer-lossless")
+        .help("Prefer lossless codecs over the other codecs")
+        .required(false)
+        .long("prefer-lossless")
+        .action(ArgAction::SetTrue);
+    let arg_ignore_channels = Arg::new("ignore-channels")
+        .help("Do not prefer tracks with more channels")
+        .required(false)
+        .long("ignore-channels")
+        .action(ArgAction::SetTrue);
+    let arg_allow_commentary = Arg::new("allow-commentary")
+        .help("Allow commentary tracks to become the default track")
+        .required(false)
+        .long("allow-commentary")
+        .action(ArgAction::SetTrue);
+    command
+        .arg(arg_languages)
+        .arg(arg_codecs)
+        .arg(arg_prefer_lossless)
+        .arg(arg_ignore_channels)
+        .arg(arg_allow_commentary)
+}
+
+fn scan_options_from_matches(sub_matches: &clap::ArgMatches) -> anyhow::Result<ScanOptions> {
+    let mut scan_options = ScanOptions::default();
+    if let Some(exts) = sub_matches.get_many::<String>("extensions") {
+        scan_options.extensions = exts
+            .map(|ext| ext.trim_start_matches('.').to_lowercase())
+            .collect();
+    }
+    let globs = |id: &str| -> Vec<String> {
+        sub_matches
+            .get_many::<String>(id)
+            .map(|globs| globs.cloned().collect())
+            .unwrap_or_default()
+    };
+    scan_options.include = build_globset(&globs("include"))?;
+    scan_options.exclude = build_globset(&globs("exclude"))?;
+    if let Some(regex) = sub_matches.get_one::<String>("regex") {
+        scan_options.regex = Some(Regex::new(regex)?);
+    }
+    if let Some(regex) = sub_matches.get_one::<String>("exclude-regex") {
+        scan_options.exclude_regex = Some(Regex::new(regex)?);
+    }
+    scan_options.max_depth = sub_matches.get_one::<usize>("max-depth").copied();
+    scan_options.follow_symlinks = sub_matches.get_flag("follow-symlinks");
+    Ok(scan_options)
+}
+
+fn sort_from_matches(sub_matches: &clap::ArgMatches) -> (GroupSort, FileSort) {
+    let group_sort = sub_matches
+        .get_one::<String>("sort-groups")
+        .and_then(|id| GroupSort::from_id(id))
+        .unwrap_or_default();
+    let file_sort = sub_matches
+        .get_one::<String>("sort-files")
+        .and_then(|id| FileSort::from_id(id))
+        .unwrap_or_default();
+    (group_sort, file_sort)
+}
+
+/// Groups of the files in the order chosen on the command line.
+fn sorted_groups<'a>(
+    files: &'a [File],
+    key_func: fn(&File) -> Vec<GroupKey>,
+    sub_matches: &clap::ArgMatches,
+) -> Vec<Group<'a>> {
+    let (group_sort, file_sort) = sort_from_matches(sub_matches);
+    let problem_files: HashSet<String> = if group_sort == GroupSort::Problems {
+        let mut config = LintConfig::default();
+        config.policy = policy_from_matches(sub_matches);
+        lint_files(files, &config)
+            .into_iter()
+            .map(|finding| finding.file_name)
+            .collect()
+    } else {
+        HashSet::new()
+    };
+    let mut groups = groupby(files, key_func);
+    sort_groups(&mut groups, group_sort, file_sort, |file| {
+        problem_files.contains(&file.file_name)
+    });
+    groups
+}
+
+/// The group given with `--group` or `--group-no`, an error if there is no such group.
+fn selected_group<'g, 'a>(
+    groups: &'g [Group<'a>],
+    sub_matches: &clap::ArgMatches,
+) -> anyhow::Result<Option<&'g Group<'a>>> {
+    if let Some(id) = sub_matches.get_one::<String>("group") {
+        return find_group(groups, id).map(Some);
+    }
+    if let Some(&number) = sub_matches.get_one::<usize>("group-no") {
+        let group = groups.get(number).with_context(|| {
+            format!(
+                "No group number {}, there are {} groups",
+                number,
+                groups.len()
+            )
+        })?;
+        return Ok(Some(group));
+    }
+    Ok(None)
+}
+
+fn cli_mode(
+    files: Vec<File>,
+    sub_name: &str,
+    sub_matches: &clap::ArgMatches,
+) -> anyhow::Result<()> {
     let track_no = sub_matches
         .get_one::<String>("track")
         .and_then(|o| o.parse::<i64>().ok());
@@ -169,81 +646,370 @@ fn cli_mode(files: Vec<File>, sub_name: &str, sub_matches: &clap::ArgMatches) {
 
     let (sel_group, _groups) = match track_type {
         TrackType::Subtitles => {
-            let groups = groupby(&files, key_sublang_subname);
+            let groups = sorted_groups(&files, key_sublang_subname, sub_matches);
             println!("SUBS");
-            if let Some(group_no) = group_no {
-                let group = groups.get(group_no).unwrap();
-                print_groups(std::slice::from_ref(group), true);
+            if let Some(group) = selected_group(&groups, sub_matches)? {
+                print_groups(std::slice::from_ref(group), true, track_type);
                 (Some(group.clone()), groups)
             } else {
                 let mut files: Vec<&File> = Vec::new();
                 groups.iter().for_each(|group| files.extend(&group.files));
-                print_groups(&groups, false);
+                print_groups(&groups, false, track_type);
                 (None, groups)
             }
         }
         TrackType::Audio => {
-            let groups = groupby(&files, key_audlang_audname);
+            let groups = sorted_groups(&files, key_audlang_audname, sub_matches);
             println!("AUDIO");
-            if let Some(group_no) = group_no {
-                let group = groups.get(group_no).unwrap();
-                print_groups(std::slice::from_ref(group), true);
+            if let Some(group) = selected_group(&groups, sub_matches)? {
+                print_groups(std::slice::from_ref(group), true, track_type);
                 (Some(group.clone()), groups)
             } else {
                 let mut files: Vec<&File> = Vec::new();
                 groups.iter().for_each(|group| files.extend(&group.files));
-                print_groups(&groups, false);
+                print_groups(&groups, false, track_type);
                 (None, groups)
             }
         }
-        TrackType::Video => return,
+        TrackType::Video => return Ok(()),
     };
 
     let sel_group = match sel_group {
         Some(group) => group,
-        None => return,
+        None => return Ok(()),
     };
 
+    let mut plan = EditPlan::new();
     if let Some(track_no) = track_no {
-        let mut track_ops = TrackOperations::new(track_type);
         if let Some(set_default_value) = set_default_value {
-            track_ops.add(track_no, TrackOperation::SetDefault(set_default_value))
+            plan.add(
+                track_type,
+                track_no,
+                TrackOperation::SetDefault(set_default_value),
+            )
         };
         if let Some(set_default_ex_value) = set_default_ex_value {
-            if set_default_value.is_none() {
-                track_ops.add(
-                    track_no,
-                    TrackOperation::SetDefaultExclusive(set_default_ex_value),
-                )
-            } else {
-                println!("Cannot use set-default-ex and set-default at the same time, exiting.");
-                return;
-            }
+            plan.add(
+                track_type,
+                track_no,
+                TrackOperation::SetDefaultExclusive(set_default_ex_value),
+            )
         };
         if let Some(set_forced_value) = set_forced_value {
-            track_ops.add(track_no, TrackOperation::SetForced(set_forced_value))
+            plan.add(
+                track_type,
+                track_no,
+                TrackOperation::SetForced(set_forced_value),
+            )
         };
         if let Some(set_enabled_value) = set_enabled_value {
-            track_ops.add(track_no, TrackOperation::SetEnabled(set_enabled_value))
+            plan.add(
+                track_type,
+                track_no,
+                TrackOperation::SetEnabled(set_enabled_value),
+            )
         };
-        if track_ops.empty() {
-            // Nothing to do
-            return;
+    }
+    if sub_matches.get_flag("add-statistics-tags") {
+        plan.add_file_op(FileOperation::AddStatisticsTags);
+    }
+    if sub_matches.get_flag("delete-statistics-tags") {
+        plan.add_file_op(FileOperation::DeleteStatisticsTags);
+    }
+    if let Some(tags_file) = sub_matches.get_one::<String>("set-global-tags") {
+        if !Path::new(tags_file).is_file() {
+            anyhow::bail!("Tags file {} does not exist", tags_file);
         }
-        // Generate and run commands
-        let mut commands: Vec<crate::command::Command> = sel_group
+        plan.add_file_op(FileOperation::SetGlobalTags(tags_file.clone()));
+    }
+    if sub_matches.get_flag("clear-global-tags") {
+        plan.add_file_op(FileOperation::ClearGlobalTags);
+    }
+    if let Some(title) = sub_matches.get_one::<String>("set-segment-title") {
+        // An empty title removes the title
+        let title = Some(title.as_str()).filter(|title| !title.is_empty());
+        plan.add_segment_op(SegmentOperation::SetTitle(title));
+    }
+    let template = match sub_matches.get_one::<String>("rename") {
+        Some(template) => match NameTemplate::parse(template) {
+            Ok(template) => Some(template),
+            Err(err) => {
+                println!("{}, exiting.", err);
+                return Ok(());
+            }
+        },
+        None => None,
+    };
+    if plan.empty() && template.is_none() {
+        // Nothing to do
+        return Ok(());
+    }
+    // Generate and run commands
+    let mut commands: Vec<crate::command::Command> = match template {
+        Some(template) => {
+            let mut scope = RenameScope::new(track_type);
+            scope.track_no = track_no.map(|track_no| track_no as usize);
+            let (commands, preview) = rename_commands(
+                &NameRule::Template(template),
+                sel_group.files.iter().copied(),
+                &scope,
+                &plan,
+            );
+            for line in preview {
+                println!("{}", line);
+            }
+            commands
+        }
+        None => sel_group
             .files
             .iter()
-            .map(|file| track_ops.generate_command(file))
-            .collect();
-        let results: std::io::Result<()> = commands.iter_mut().try_for_each(|cmd| cmd.run());
-        match results {
-            Ok(_) => {}
-            Err(err) => println!("Error when calling command - aborting: {}", err),
+            .map(|file| plan.generate_command(file))
+            .collect(),
+    };
+    if sub_matches.get_flag("dry-run") {
+        for command in &commands {
+            if let Some(cmd_string) = command.to_cmd_string() {
+                println!("{}", cmd_string);
+            }
+        }
+        return Ok(());
+    }
+    run_commands(&mut commands)
+}
+
+/// Runs all commands and reports the ones that failed. Stops at the first command that could
+/// not be started, but runs the remaining commands when one exits with an error.
+fn run_commands(commands: &mut [crate::command::Command]) -> anyhow::Result<()> {
+    let mut failed = 0;
+    for command in commands.iter_mut() {
+        command
+            .run()
+            .with_context(|| format!("Could not run {}", command.executable))?;
+        let output = command.output.as_ref().expect("command has run");
+        if !output.status.success() {
+            failed += 1;
+            eprintln!(
+                "Command failed ({}): {}",
+                output.status,
+                command.to_cmd_string().unwrap_or_default()
+            );
+            // mkvpropedit reports errors on stdout
+            eprint!("{}{}", output.stdout, output.stderr);
+        }
+    }
+    if failed > 0 {
+        anyhow::bail!("{} of {} commands failed", failed, commands.len());
+    }
+    Ok(())
+}
+
+fn policy_from_matches(sub_matches: &clap::ArgMatches) -> DefaultPolicy {
+    let languages: Vec<String> = sub_matches
+        .get_many::<String>("languages")
+        .map(|langs| langs.cloned().collect())
+        .unwrap_or_default();
+    let mut codecs: Vec<String> = Vec::new();
+    if sub_matches.get_flag("prefer-lossless") {
+        codecs.extend(LOSSLESS_CODECS.iter().map(|codec| codec.to_string()));
+    }
+    if let Some(pref_codecs) = sub_matches.get_many::<String>("codecs") {
+        codecs.extend(pref_codecs.cloned());
+    }
+    DefaultPolicy {
+        languages,
+        codecs,
+        prefer_channels: !sub_matches.get_flag("ignore-channels"),
+        avoid_commentary: !sub_matches.get_flag("allow-commentary"),
+    }
+}
+
+fn choose_default_mode(files: Vec<File>, sub_matches: &clap::ArgMatches) -> anyhow::Result<()> {
+    let policy = policy_from_matches(sub_matches);
+    let track_type = track_type_from_matches(sub_matches);
+    let dry_run = sub_matches.get_flag("dry-run");
+
+    let mut commands: Vec<crate::command::Command> = Vec::new();
+    for file in &files {
+        let tracks = file.tracks(track_type);
+        let Some(chosen) = policy.choose_if_changed(tracks) else {
+            continue;
+        };
+        println!(
+            "{}: track {} ({}) becomes the default",
+            file.file_name,
+            chosen,
+            tracks[chosen].name.as_deref().unwrap_or("unnamed")
+        );
+        let mut plan = EditPlan::new();
+        plan.add(
+            track_type,
+            chosen as i64,
+            TrackOperation::SetDefaultExclusive(true),
+        );
+        commands.push(plan.generate_command(file));
+    }
+
+    if dry_run {
+        for command in &commands {
+            if let Some(cmd_string) = command.to_cmd_string() {
+                println!("{}", cmd_string);
+            }
         }
+        return Ok(());
+    }
+    run_commands(&mut commands)
+}
+
+fn track_type_from_matches(sub_matches: &clap::ArgMatches) -> TrackType {
+    match sub_matches.get_one::<String>("type").map(String::as_str) {
+        Some("subs") => TrackType::Subtitles,
+        _ => TrackType::Audio,
     }
 }
 
-fn tui_mode(files: Vec<File>) {
-    main_loop(files).unwrap();
+fn analyze_mode(files: Vec<File>, sub_matches: &clap::ArgMatches) -> anyhow::Result<()> {
+    let track_type = track_type_from_matches(sub_matches);
+    let count_events = sub_matches.get_flag("count-events");
+    if count_events && which::which("mkvextract").is_err() {
+        println!("Could not find mkvextract, exiting.");
+        return Ok(());
+    }
+
+    let mut commands: Vec<crate::command::Command> = Vec::new();
+    for file in &files {
+        let suggestions = suggest_for_file(file, track_type, count_events);
+        if suggestions.is_empty() {
+            continue;
+        }
+        println!("{}", file.file_name);
+        let mut plan = EditPlan::new();
+        for suggestion in &suggestions {
+            println!("    {}", suggestion.description());
+            plan.add(
+                track_type,
+                suggestion.track_no as i64,
+                suggestion.to_operation(),
+            );
+        }
+        commands.push(plan.generate_command(file));
+    }
+
+    if !sub_matches.get_flag("apply") {
+        return Ok(());
+    }
+    run_commands(&mut commands)
+}
+
+fn rename_mode(files: Vec<File>, sub_matches: &clap::ArgMatches) -> anyhow::Result<()> {
+    let rule = match sub_matches.get_one::<String>("template") {
+        Some(template) => NameRule::Template(NameTemplate::parse(template)?),
+        None => NameRule::Replace(NameReplace::new(
+            sub_matches.get_one::<String>("find").unwrap(),
+            sub_matches.get_one::<String>("replace").unwrap(),
+        )?),
+    };
+    let track_type = track_type_from_matches(sub_matches);
+    let mut scope = RenameScope::new(track_type);
+    scope.language = sub_matches.get_one::<String>("language").cloned();
+
+    let (mut commands, preview) = rename_commands(&rule, &files, &scope, &EditPlan::new());
+    for line in &preview {
+        println!("{}", line);
+    }
+    println!("{} files affected", commands.len());
+
+    if !sub_matches.get_flag("apply") {
+        return Ok(());
+    }
+    run_commands(&mut commands)
+}
+
+fn lint_mode(
+    files: Vec<File>,
+    scan_errors: &[ScanError],
+    sub_matches: &clap::ArgMatches,
+) -> anyhow::Result<()> {
+    let mut config = LintConfig::default();
+    config.policy = policy_from_matches(sub_matches);
+    if let Some(settings) = sub_matches.get_many::<String>("severity") {
+        for setting in settings {
+            config.set_from_str(setting)?;
+        }
+    }
+    let findings = lint_files(&files, &config);
+    match sub_matches.get_one::<String>("format").map(String::as_str) {
+        Some("json") => println!("{}", json_report(&findings, scan_errors)),
+        _ => print!("{}", text_report(&findings, scan_errors)),
+    }
+
+    let mut fixed = false;
+    if sub_matches.get_flag("fix") {
+        let mut commands = fix_commands(&findings, &files);
+        if sub_matches.get_flag("dry-run") {
+            for command in &commands {
+                if let Some(cmd_string) = command.to_cmd_string() {
+                    println!("{}", cmd_string);
+                }
+            }
+        } else {
+            match run_commands(&mut commands) {
+                Ok(()) => fixed = true,
+                Err(err) => eprintln!("{:#}", err),
+            }
+        }
+    }
+
+    let fail_on = sub_matches
+        .get_one::<String>("fail-on")
+        .and_then(|id| Severity::from_id(id))
+        .unwrap_or(Severity::Warning);
+    // Files that could not be read always fail, findings that were fixed successfully do not
+    let failed = !scan_errors.is_empty()
+        || findings
+            .iter()
+            .filter(|finding| !(fixed && finding.fix.is_some()))
+            .any(|finding| finding.severity >= fail_on);
+    if failed {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn tui_mode(
+    files: Vec<File>,
+    sub_matches: &clap::ArgMatches,
+    scan_errors: usize,
+) -> anyhow::Result<()> {
+    let (group_sort, file_sort) = sort_from_matches(sub_matches);
+    let keymap = match sub_matches.get_one::<String>("keymap") {
+        Some(path) => Keymap::from_path(Path::new(path))?,
+        None => match config_file("keymap.toml") {
+            Some(path) => Keymap::from_path(&path)?,
+            None => Keymap::default(),
+        },
+    };
+    for conflict in keymap.conflicts() {
+        eprintln!("Warning: {}", conflict);
+    }
+    // Configured themes take precedence over NO_COLOR
+    let no_color = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+    let theme = match sub_matches.get_one::<String>("theme") {
+        Some(name) => Theme::from_name_or_path(name)?,
+        None => match config_file("theme.toml") {
+            Some(path) => Theme::from_path(&path)?,
+            None if no_color => Theme::mono(),
+            None => Theme::default(),
+        },
+    };
+    main_loop(
+        files,
+        policy_from_matches(sub_matches),
+        scan_errors,
+        group_sort,
+        file_sort,
+        keymap,
+        theme,
+    )
+    .unwrap();
+    Ok(())
 }
@@ -0,0 +1,410 @@
+use crate::command::Command;
+use crate::file::{File, Track, TrackType};
+use crate::language::language_name;
+use crate::track_operations::{EditPlan, TrackOperation};
+
+use anyhow::{Context, Result, anyhow, bail};
+use regex::Regex;
+
+const FIELDS: [&str; 7] = [
+    "lang",
+    "lang_name",
+    "name",
+    "codec",
+    "channels",
+    "bitrate",
+    "sampling_frequency",
+];
+const FLAGS: [&str; 5] = ["default", "forced", "enabled", "sdh", "commentary"];
+
+#[derive(Clone, PartialEq, Debug)]
+enum Part {
+    Literal(String),
+    /// Attribute of the track, e.g. `{lang_name}`.
+    Field(&'static str),
+    /// Text that is only inserted if a flag is set, e.g. `{forced?" (Forced)"}`
+    /// or `{sdh?"SDH":"Full"}`.
+    Conditional {
+        flag: &'static str,
+        then_text: String,
+        else_text: String,
+    },
+}
+
+/// Template for track names, e.g. `{lang_name} {channels} {codec}{forced?" (Forced)"}`.
+/// Literal braces are written as `{{` and `}}`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct NameTemplate {
+    parts: Vec<Part>,
+}
+
+/// Parses a double quoted string, returns the string and the rest of the input.
+fn parse_quoted(input: &str) -> Result<(String, &str)> {
+    let input = input.trim_start();
+    let rest = input
+        .strip_prefix('"')
+        .ok_or_else(|| anyhow!("Expected a quoted text, got {}", input))?;
+    let end = rest
+        .find('"')
+        .ok_or_else(|| anyhow!("Missing closing quote in {}", input))?;
+    Ok((rest[..end].to_owned(), &rest[end + 1..]))
+}
+
+fn parse_placeholder(placeholder: &str) -> Result<Part> {
+    let Some((flag, texts)) = placeholder.split_once('?') else {
+        let field = placeholder.trim();
+        return FIELDS
+            .iter()
+            .find(|known| **known == field)
+            .map(|known| Part::Field(known))
+            .ok_or_else(|| {
+                anyhow!(
+                    "Unknown field {{{}}}, known fields: {}",
+                    field,
+                    FIELDS.join(", ")
+                )
+            });
+    };
+    let flag = flag.trim();
+    let flag = FLAGS
+        .iter()
+        .find(|known| **known == flag)
+        .ok_or_else(|| anyhow!("Unknown flag {}, known flags: {}", flag, FLAGS.join(", ")))?;
+    let (then_text, rest) = parse_quoted(texts)?;
+    let rest = rest.trim();
+    let else_text = match rest.strip_prefix(':') {
+        Some(else_part) => {
+            let (else_text, rest) = parse_quoted(else_part)?;
+            if !rest.trim().is_empty() {
+                bail!("Unexpected {} in {{{}}}", rest.trim(), placeholder);
+            }
+            else_text
+        }
+        None if rest.is_empty() => String::new(),
+        None => bail!("Unexpected {} in {{{}}}", rest, placeholder),
+    };
+    Ok(Part::Conditional {
+        flag,
+        then_text,
+        else_text,
+    })
+}
+
+impl NameTemplate {
+    pub fn parse(template: &str) -> Result<Self> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = template.char_indices().peekable();
+        while let Some((pos, c)) = chars.next() {
+            match c {
+                '{' if chars.peek().map(|(_, next)| *next) == Some('{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek().map(|(_, next)| *next) == Some('}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    // Quoted texts may contain braces, so look for the first unquoted '}'
+                    let mut in_quotes = false;
+                    let end = template[pos + 1..]
+                        .char_indices()
+                        .find(|(_, c)| {
+                            if *c == '"' {
+                                in_quotes = !in_quotes;
+                            }
+                            *c == '}' && !in_quotes
+                        })
+                        .map(|(offset, _)| pos + 1 + offset)
+                        .ok_or_else(|| anyhow!("Missing closing brace in {}", template))?;
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(parse_placeholder(&template[pos + 1..end])?);
+                    while chars.peek().is_some_and(|(next_pos, _)| *next_pos <= end) {
+                        chars.next();
+                    }
+                }
+                '}' => bail!("Unmatched closing brace in {}", template),
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+        Ok(NameTemplate { parts })
+    }
+
+    fn field(track: &Track, field: &str) -> Option<String> {
+        match field {
+            "lang" => track.language.clone(),
+            "lang_name" => track.language.as_deref().map(|lang| {
+                language_name(lang)
+                    .map(str::to_owned)
+                    .unwrap_or_else(|| lang.to_owned())
+            }),
+            "name" => track.name.clone(),
+            "codec" => track.codec.clone(),
+            "channels" => track.channels_string(),
+            "bitrate" => track.bitrate_string(),
+            "sampling_frequency" => track.sampling_frequency_string(),
+            _ => None,
+        }
+    }
+
+    fn flag(track: &Track, flag: &str) -> bool {
+        match flag {
+            "default" => track.default,
+            "forced" => track.forced,
+            "enabled" => track.enabled,
+            "sdh" => track.hearing_impaired,
+            "commentary" => track.commentary,
+            _ => false,
+        }
+    }
+
+    /// Name of the track according to the template. Whitespace is normalized, so missing
+    /// attributes do not leave gaps. Returns None if the name would be empty.
+    pub fn render(&self, track: &Track) -> Option<String> {
+        let mut name = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(text) => name.push_str(text),
+                Part::Field(field) => {
+                    if let Some(value) = Self::field(track, field) {
+                        name.push_str(&value);
+                    }
+                }
+                Part::Conditional {
+                    flag,
+                    then_text,
+                    else_text,
+                } => {
+                    if Self::flag(track, flag) {
+                        name.push_str(then_text);
+                    } else {
+                        name.push_str(else_text);
+                    }
+                }
+            }
+        }
+        let name = name.split_whitespace().collect::<Vec<_>>().join(" ");
+        if name.is_empty() { None } else { Some(name) }
+    }
+}
+
+/// A track whose name changes.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Rename {
+    pub track_no: usize,
+    pub old_name: Option<String>,
+    pub new_name: Option<String>,
+}
+
+impl Rename {
+    pub fn description(&self) -> String {
+        let quote = |name: &Option<String>| match name {
+            Some(name) => format!("\"{}\"", name),
+            None => "(none)".to_owned(),
+        };
+        format!(
+            "track {}: {} -> {}",
+            self.track_no,
+            quote(&self.old_name),
+            quote(&self.new_name)
+        )
+    }
+}
+
+/// Find and replace on track names. The replacement may refer to capture groups,
+/// e.g. `$1` or `${name}`.
+#[derive(Clone, Debug)]
+pub struct NameReplace {
+    regex: Regex,
+    replacement: String,
+}
+
+impl NameReplace {
+    pub fn new(pattern: &str, replacement: &str) -> Result<Self> {
+        let regex = Regex::new(pattern)
+            .with_context(|| format!("Invalid regular expression {}", pattern))?;
+        Ok(NameReplace {
+            regex,
+            replacement: replacement.to_owned(),
+        })
+    }
+
+    /// Name of the track with all matches replaced, None if the name would be empty.
+    pub fn apply(&self, track: &Track) -> Option<String> {
+        let name = track.name.as_deref()?;
+        let name = self.regex.replace_all(name, self.replacement.as_str());
+        let name = name.trim();
+        if name.is_empty() {
+            None
+        } else {
+            Some(name.to_owned())
+        }
+    }
+}
+
+/// How the new name of a track is determined.
+#[derive(Clone, Debug)]
+pub enum NameRule {
+    Template(NameTemplate),
+    Replace(NameReplace),
+}
+
+impl NameRule {
+    pub fn new_name(&self, track: &Track) -> Option<String> {
+        match self {
+            NameRule::Template(template) => template.render(track),
+            NameRule::Replace(replace) => replace.apply(track),
+        }
+    }
+}
+
+/// Tracks that are renamed.
+#[derive(Clone, Debug)]
+pub struct RenameScope {
+    pub track_type: TrackType,
+    /// Only rename this track of each file.
+    pub track_no: Option<usize>,
+    /// Only rename tracks with this language.
+    pub language: Option<String>,
+}
+
+impl RenameScope {
+    pub fn new(track_type: TrackType) -> Self {
+        RenameScope {
+            track_type,
+            track_no: None,
+            language: None,
+        }
+    }
+
+    fn contains(&self, track_no: usize, track: &Track) -> bool {
+        self.track_no.is_none_or(|no| no == track_no)
+            && self
+                .language
+                .as_ref()
+                .is_none_or(|language| track.language.as_ref() == Some(language))
+    }
+}
+
+/// Renames of the tracks of a file, only tracks whose name changes are returned.
+pub fn file_renames(rule: &NameRule, file: &File, scope: &RenameScope) -> Vec<Rename> {
+    file.tracks(scope.track_type)
+        .iter()
+        .enumerate()
+        .filter(|(no, track)| scope.contains(*no, track))
+        .map(|(no, track)| Rename {
+            track_no: no,
+            old_name: track.name.clone(),
+            new_name: rule.new_name(track),
+        })
+        .filter(|rename| rename.old_name != rename.new_name)
+        .collect()
+}
+
+/// Commands that rename the tracks of the files together with a preview of old and new
+/// names per file. The operations of `base_plan` are added to every command.
+pub fn rename_commands<'a>(
+    rule: &NameRule,
+    files: impl IntoIterator<Item = &'a File>,
+    scope: &RenameScope,
+    base_plan: &EditPlan,
+) -> (Vec<Command>, Vec<String>) {
+    let mut commands = Vec::new();
+    let mut preview = Vec::new();
+    for file in files {
+        let renames = file_renames(rule, file, scope);
+        if renames.is_empty() && base_plan.empty() {
+            continue;
+        }
+        let mut plan = base_plan.clone();
+        for rename in &renames {
+            plan.add(
+                scope.track_type,
+                rename.track_no as i64,
+                TrackOperation::SetTitle(rename.new_name.as_deref()),
+            );
+        }
+        commands.push(plan.generate_command(file));
+        if !renames.is_empty() {
+            preview.push(file.file_name.clone());
+            preview.extend(
+                renames
+                    .iter()
+                    .map(|rename| format!("    {}", rename.description())),
+            );
+        }
+    }
+    (commands, preview)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(name: Option<&str>, forced: bool) -> Track {
+        let json: serde_json::Value = serde_json::from_str(&format!(
+            r#"{{
+                "codec": "AC-3",
+                "id": 1,
+                "properties": {{
+                    "audio_channels": 6,
+                    "default_track": false,
+                    "enabled_track": true,
+                    "forced_track": {},
+                    "language": "eng"{}
+                }},
+                "type": "audio"
+            }}"#,
+            forced,
+            name.map(|name| format!(r#", "track_name": "{}""#, name))
+                .unwrap_or_default()
+        ))
+        .unwrap();
+        Track::from_json(&json).unwrap()
+    }
+
+    #[test]
+    fn test_render() {
+        let template =
+            NameTemplate::parse(r#"{lang_name} {channels} {codec}{ forced?" (Forced)"}"#).unwrap();
+        assert_eq!(
+            template.render(&track(Some("ENG 5.1"), false)).as_deref(),
+            Some("English 5.1 AC-3")
+        );
+        assert_eq!(
+            template.render(&track(None, true)).as_deref(),
+            Some("English 5.1 AC-3 (Forced)")
+        );
+        let template = NameTemplate::parse(r#"{{{sdh?"SDH":"Full"}}} {bitrate}"#).unwrap();
+        assert_eq!(
+            template.render(&track(None, false)).as_deref(),
+            Some("{Full}")
+        );
+        assert!(NameTemplate::parse("{unknown}").is_err());
+        assert!(NameTemplate::parse(r#"{forced?"x}"#).is_err());
+    }
+
+    #[test]
+    fn test_replace() {
+        let replace = NameReplace::new(r"\bSDH\b", "(SDH)").unwrap();
+        assert_eq!(
+            replace.apply(&track(Some("English SDH"), false)).as_deref(),
+            Some("English (SDH)")
+        );
+        let replace = NameReplace::new(r"^(.*?)\s*\[[^\]]*\]$", "$1").unwrap();
+        assert_eq!(
+            replace
+                .apply(&track(Some("English [ReleaseGroup]"), false))
+                .as_deref(),
+            Some("English")
+        );
+        assert_eq!(replace.apply(&track(None, false)), None);
+        assert!(NameReplace::new("(", "").is_err());
+    }
+}
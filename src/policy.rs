@@ -0,0 +1,276 @@
+use crate::analyze::suggest_for_file;
+use crate::command::Command;
+use crate::file::{File, Track, TrackType};
+use crate::track_operations::{EditPlan, TrackOperation};
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::path::Path;
+
+/// Codecs that are preferred when lossless audio is requested, best first.
+pub const LOSSLESS_CODECS: [&str; 4] = ["TrueHD", "DTS-HD Master Audio", "FLAC", "PCM"];
+
+/// Ranked criteria to choose the default track of a file.
+///
+/// Tracks are compared by (in this order): commentary, language preference,
+/// channel count and codec preference. Remaining ties keep the track order.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DefaultPolicy {
+    pub languages: Vec<String>,
+    pub codecs: Vec<String>,
+    pub prefer_channels: bool,
+    pub avoid_commentary: bool,
+}
+
+impl Default for DefaultPolicy {
+    fn default() -> Self {
+        DefaultPolicy {
+            languages: Vec::new(),
+            codecs: Vec::new(),
+            prefer_channels: true,
+            avoid_commentary: true,
+        }
+    }
+}
+
+impl DefaultPolicy {
+    /// Returns the index of the track that should become the default track.
+    pub fn choose(&self, tracks: &[Track]) -> Option<usize> {
+        tracks
+            .iter()
+            .enumerate()
+            .min_by_key(|(idx, track)| self.rank(track, *idx))
+            .map(|(idx, _)| idx)
+    }
+
+    /// Like `choose`, but returns None if the chosen track already is the only default track.
+    pub fn choose_if_changed(&self, tracks: &[Track]) -> Option<usize> {
+        let chosen = self.choose(tracks)?;
+        let already_default = tracks
+            .iter()
+            .enumerate()
+            .all(|(idx, track)| track.default == (idx == chosen));
+        if already_default { None } else { Some(chosen) }
+    }
+
+    fn rank(&self, track: &Track, idx: usize) -> (bool, usize, i64, usize, usize) {
+        let commentary = self.avoid_commentary && is_commentary(track);
+        let language = track
+            .language
+            .as_ref()
+            .and_then(|lang| self.languages.iter().position(|pref| pref == lang))
+            .unwrap_or(self.languages.len());
+        let channels = if self.prefer_channels {
+            -track.audio_channels.unwrap_or(0)
+        } else {
+            0
+        };
+        let codec = track
+            .codec
+            .as_ref()
+            .and_then(|codec| {
+                let codec = codec.to_lowercase();
+                self.codecs
+                    .iter()
+                    .position(|pref| codec.contains(&pref.to_lowercase()))
+            })
+            .unwrap_or(self.codecs.len());
+        (commentary, language, channels, codec, idx)
+    }
+}
+
+/// Rules that are applied to files automatically, loaded from a TOML file:
+///
+/// ```toml
+/// apply_suggestions = true
+///
+/// [audio]
+/// languages = ["jpn", "eng"]
+/// codecs = ["TrueHD", "FLAC"]
+///
+/// [subtitles]
+/// languages = ["eng"]
+/// ```
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PolicyRules {
+    /// Default track policy for audio tracks, the default is left untouched if unset.
+    pub audio: Option<DefaultPolicy>,
+    pub subtitles: Option<DefaultPolicy>,
+    /// Set the forced, hearing impaired and commentary flags suggested by track names.
+    pub apply_suggestions: bool,
+}
+
+impl PolicyRules {
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Reading policy file {} failed", path.display()))?;
+        let rules = toml::from_str(&content)
+            .with_context(|| format!("Parsing policy file {} failed", path.display()))?;
+        Ok(rules)
+    }
+
+    /// Commands that apply the rules to the file, empty if the file already complies.
+    pub fn commands(&self, file: &File) -> Vec<Command> {
+        let mut plan = EditPlan::new();
+        for (track_type, policy) in [
+            (TrackType::Audio, &self.audio),
+            (TrackType::Subtitles, &self.subtitles),
+        ] {
+            if let Some(policy) = policy
+                && let Some(chosen) = policy.choose_if_changed(file.tracks(track_type))
+            {
+                plan.add(
+                    track_type,
+                    chosen as i64,
+                    TrackOperation::SetDefaultExclusive(true),
+                );
+            }
+            if self.apply_suggestions {
+                for suggestion in suggest_for_file(file, track_type, false) {
+                    plan.add(
+                        track_type,
+                        suggestion.track_no as i64,
+                        suggestion.to_operation(),
+                    );
+                }
+            }
+        }
+        if plan.empty() {
+            Vec::new()
+        } else {
+            vec![plan.generate_command(file)]
+        }
+    }
+}
+
+pub fn is_commentary(track: &Track) -> bool {
+    track.commentary
+        || track
+            .name
+            .as_ref()
+            .map(|name| name.to_lowercase().contains("commentary"))
+            .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file::TrackType;
+
+    fn audio_track(id: i64, lang: &str, name: Option<&str>, channels: i64, codec: &str) -> Track {
+        Track {
+            name: name.map(String::from),
+            language: Some(lang.to_owned()),
+            ttype: TrackType::Audio,
+            id,
+            default: false,
+            forced: false,
+            enabled: true,
+            hearing_impaired: false,
+            commentary: false,
+            codec: Some(codec.to_owned()),
+            codec_id: None,
+            audio_channels: Some(channels),
+            audio_sampling_frequency: Some(48000),
+            bitrate: None,
+            text_subtitles: None,
+        }
+    }
+
+    #[test]
+    fn test_choose_default() {
+        let tracks = vec![
+            audio_track(1, "eng", None, 2, "AAC"),
+            audio_track(2, "jpn", Some("Commentary"), 8, "TrueHD"),
+            audio_track(3, "jpn", None, 2, "FLAC"),
+            audio_track(4, "jpn", None, 6, "AC-3"),
+        ];
+        let mut policy = DefaultPolicy {
+            languages: vec!["jpn".to_owned(), "eng".to_owned()],
+            ..DefaultPolicy::default()
+        };
+        assert_eq!(policy.choose(&tracks), Some(3));
+
+        policy.prefer_channels = false;
+        policy.codecs = LOSSLESS_CODECS.iter().map(|c| c.to_string()).collect();
+        assert_eq!(policy.choose(&tracks), Some(2));
+
+        policy.avoid_commentary = false;
+        assert_eq!(policy.choose(&tracks), Some(1));
+        assert_eq!(policy.choose(&[]), None);
+    }
+
+    #[test]
+    fn test_policy_rules_from_toml() {
+        let rules: PolicyRules = toml::from_str(
+            r#"
+            apply_suggestions = true
+
+            [audio]
+            languages = ["jpn", "eng"]
+            avoid_commentary = false
+            "#,
+        )
+        .unwrap();
+        assert!(rules.apply_suggestions);
+        assert!(rules.subtitles.is_none());
+        let audio = rules.audio.unwrap();
+        assert_eq!(audio.languages, vec!["jpn", "eng"]);
+        assert!(audio.prefer_channels);
+        assert!(!audio.avoid_commentary);
+        // Misspelled keys are errors instead of being ignored
+        assert!(toml::from_str::<PolicyRules>("apply_sugestions = true").is_err());
+        assert!(toml::from_str::<PolicyRules>("[audio]\nlanguage = [\"jpn\"]").is_err());
+    }
+
+    #[test]
+    fn test_policy_rules_commands() {
+        let file = File::from_json_str(
+            r#"{
+                "file_name": "movie.mkv",
+                "tracks": [
+                    {"id": 0, "type": "audio", "properties": {"language": "eng",
+                        "default_track": true, "forced_track": false, "enabled_track": true}},
+                    {"id": 1, "type": "audio", "properties": {"language": "jpn",
+                        "default_track": false, "forced_track": false, "enabled_track": true}},
+                    {"id": 2, "type": "subtitles", "properties": {"language": "eng",
+                        "track_name": "Signs", "default_track": false, "forced_track": false,
+                        "enabled_track": true}}
+                ]
+            }"#,
+        )
+        .unwrap();
+        let mut rules: PolicyRules = toml::from_str("[audio]\nlanguages = [\"jpn\"]").unwrap();
+        let commands = rules.commands(&file);
+        assert_eq!(commands.len(), 1);
+        assert_eq!(
+            commands[0].arguments,
+            vec![
+                "--edit",
+                "track:@1",
+                "--set",
+                "flag-default=0",
+                "--edit",
+                "track:@2",
+                "--set",
+                "flag-default=1",
+                "movie.mkv",
+            ]
+        );
+
+        // Suggestions are merged into the same command
+        rules.apply_suggestions = true;
+        let commands = rules.commands(&file);
+        assert_eq!(commands.len(), 1);
+        assert!(commands[0].arguments.contains(&"flag-forced=1".to_owned()));
+
+        // Nothing to do for a file that already complies
+        rules.apply_suggestions = false;
+        rules.audio = Some(DefaultPolicy {
+            languages: vec!["eng".to_owned()],
+            ..DefaultPolicy::default()
+        });
+        assert!(rules.commands(&file).is_empty());
+    }
+}
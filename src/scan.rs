@@ -0,0 +1,359 @@
+use anyhow::{Context, Result};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use regex::Regex;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::io::Read;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// Matroska based extensions that are scanned if none are specified.
+pub const DEFAULT_EXTENSIONS: [&str; 5] = ["mkv", "mka", "mks", "mk3d", "webm"];
+
+/// Name of the files containing glob patterns (one per line) of entries to skip.
+/// Patterns are relative to the directory containing the ignore file.
+pub const IGNORE_FILE_NAME: &str = ".mtxstuffignore";
+
+#[derive(Clone, Debug)]
+pub struct ScanOptions {
+    /// Lower case extensions without the leading dot.
+    pub extensions: Vec<String>,
+    /// If set, only files whose path relative to the scanned directory matches are scanned.
+    pub include: Option<GlobSet>,
+    pub exclude: Option<GlobSet>,
+    /// If set, only files whose full path matches are scanned.
+    pub regex: Option<Regex>,
+    pub exclude_regex: Option<Regex>,
+    pub max_depth: Option<usize>,
+    pub follow_symlinks: bool,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        ScanOptions {
+            extensions: DEFAULT_EXTENSIONS.iter().map(|e| e.to_string()).collect(),
+            include: None,
+            exclude: None,
+            regex: None,
+            exclude_regex: None,
+            max_depth: None,
+            follow_symlinks: false,
+        }
+    }
+}
+
+/// An entry that could not be scanned.
+#[derive(Debug)]
+pub struct ScanError {
+    pub path: Option<PathBuf>,
+    pub message: String,
+}
+
+impl std::fmt::Display for ScanError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.path {
+            Some(ref path) => write!(f, "{}: {}", path.display(), self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ScanResult {
+    pub files: Vec<PathBuf>,
+    pub errors: Vec<ScanError>,
+}
+
+pub fn build_globset<S: AsRef<str>>(patterns: &[S]) -> Result<Option<GlobSet>> {
+    if patterns.is_empty() {
+        return Ok(None);
+    }
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob = Glob::new(pattern.as_ref())
+            .with_context(|| format!("Invalid glob pattern {}", pattern.as_ref()))?;
+        builder.add(glob);
+    }
+    Ok(Some(builder.build()?))
+}
+
+impl ScanOptions {
+    fn matches_extension(&self, path: &Path) -> bool {
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| {
+                let ext = ext.to_lowercase();
+                self.extensions.contains(&ext)
+            })
+            .unwrap_or(false)
+    }
+
+    /// Whether a file below root would be scanned, ignore files are not considered.
+    pub fn matches(&self, root: &Path, path: &Path) -> bool {
+        self.matches_extension(path) && self.matches_filters(root, path)
+    }
+
+    fn matches_filters(&self, root: &Path, path: &Path) -> bool {
+        let relative = path.strip_prefix(root).unwrap_or(path);
+        if let Some(ref include) = self.include
+            && !include.is_match(relative)
+        {
+            return false;
+        }
+        if let Some(ref exclude) = self.exclude
+            && exclude.is_match(relative)
+        {
+            return false;
+        }
+        let path_str = path.to_string_lossy();
+        if let Some(ref regex) = self.regex
+            && !regex.is_match(&path_str)
+        {
+            return false;
+        }
+        if let Some(ref exclude_regex) = self.exclude_regex
+            && exclude_regex.is_match(&path_str)
+        {
+            return false;
+        }
+        true
+    }
+}
+
+/// Caches the patterns of the ignore files per directory.
+#[derive(Default)]
+pub struct IgnoreFiles {
+    dirs: HashMap<PathBuf, Option<GlobSet>>,
+    pub errors: Vec<ScanError>,
+}
+
+impl IgnoreFiles {
+    fn load(&mut self, dir: &Path) -> Option<&GlobSet> {
+        if !self.dirs.contains_key(dir) {
+            let globset = self.read(dir);
+            self.dirs.insert(dir.to_path_buf(), globset);
+        }
+        self.dirs.get(dir).and_then(Option::as_ref)
+    }
+
+    fn read(&mut self, dir: &Path) -> Option<GlobSet> {
+        let ignore_path = dir.join(IGNORE_FILE_NAME);
+        if !ignore_path.is_file() {
+            return None;
+        }
+        let patterns: Vec<String> = match std::fs::read_to_string(&ignore_path) {
+            Ok(content) => content
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(String::from)
+                .collect(),
+            Err(err) => {
+                self.errors.push(ScanError {
+                    path: Some(ignore_path),
+                    message: err.to_string(),
+                });
+                return None;
+            }
+        };
+        match build_globset(&patterns) {
+            Ok(globset) => globset,
+            Err(err) => {
+                self.errors.push(ScanError {
+                    path: Some(ignore_path),
+                    message: err.to_string(),
+                });
+                None
+            }
+        }
+    }
+
+    /// Checks the ignore files of all directories between root and the path.
+    fn is_ignored(&mut self, root: &Path, path: &Path) -> bool {
+        let mut dir = path.parent();
+        while let Some(cur_dir) = dir {
+            if let Some(globset) = self.load(cur_dir)
+                && let Ok(relative) = path.strip_prefix(cur_dir)
+                && globset.is_match(relative)
+            {
+                return true;
+            }
+            if cur_dir == root {
+                break;
+            }
+            dir = cur_dir.parent();
+        }
+        false
+    }
+
+    /// Whether the path or one of its parent directories below root is ignored. Unlike
+    /// scanning, which skips ignored directories as a whole, this checks a single path.
+    pub fn is_path_ignored(&mut self, root: &Path, path: &Path) -> bool {
+        let Ok(relative) = path.strip_prefix(root) else {
+            return false;
+        };
+        let mut current = root.to_path_buf();
+        for component in relative.components() {
+            current.push(component);
+            if self.is_ignored(root, &current) {
+                return true;
+            }
+        }
+        false
+    }
+}
+
+pub fn get_files_recursively(path: &Path, options: &ScanOptions) -> ScanResult {
+    let mut result = ScanResult::default();
+    let ignore_files = RefCell::new(IgnoreFiles::default());
+    let mut walker = WalkDir::new(path).follow_links(options.follow_symlinks);
+    if let Some(max_depth) = options.max_depth {
+        walker = walker.max_depth(max_depth);
+    }
+    let walker = walker
+        .into_iter()
+        .filter_entry(|e| e.depth() == 0 || !ignore_files.borrow_mut().is_ignored(path, e.path()));
+    for entry in walker {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(err) => {
+                result.errors.push(ScanError {
+                    path: err.path().map(Path::to_path_buf),
+                    message: err.to_string(),
+                });
+                continue;
+            }
+        };
+        // Symlinks are only reported as files if they are followed
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let file_path = entry.path();
+        if options.matches(path, file_path) {
+            result.files.push(file_path.to_path_buf());
+        }
+    }
+    result.errors.extend(ignore_files.into_inner().errors);
+    result
+}
+
+/// Scans all directories and adds the files given explicitly. Explicit files
+/// are not filtered, files found more than once are only returned once.
+pub fn collect_files(paths: &[PathBuf], options: &ScanOptions) -> ScanResult {
+    let mut result = ScanResult::default();
+    for path in paths {
+        if path.is_dir() {
+            let dir_result = get_files_recursively(path, options);
+            result.files.extend(dir_result.files);
+            result.errors.extend(dir_result.errors);
+        } else if path.is_file() {
+            result.files.push(path.clone());
+        } else {
+            result.errors.push(ScanError {
+                path: Some(path.clone()),
+                message: "No such file or directory".to_owned(),
+            });
+        }
+    }
+    let mut seen = std::collections::HashSet::new();
+    result.files.retain(|file| seen.insert(file.clone()));
+    result
+}
+
+/// Reads a NUL-separated list of paths as produced by `find -print0` or `fd -0`.
+/// Lists without any NUL byte are split by lines instead.
+pub fn read_file_list<R: Read>(mut reader: R) -> Result<Vec<PathBuf>> {
+    let mut content = Vec::new();
+    reader
+        .read_to_end(&mut content)
+        .context("Reading the file list failed")?;
+    let separator = if content.contains(&0) { 0 } else { b'\n' };
+    let paths = content
+        .split(|byte| *byte == separator)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| PathBuf::from(OsStr::from_bytes(entry)))
+        .collect();
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_get_files_recursively() {
+        let root = std::env::temp_dir().join(format!("mtxstuff-scan-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("season1/extras")).unwrap();
+        for file in [
+            "a.mkv",
+            "b.MKV",
+            "c.txt",
+            "season1/d.mka",
+            "season1/sample.mkv",
+            "season1/extras/e.mkv",
+        ] {
+            fs::write(root.join(file), b"").unwrap();
+        }
+        fs::write(root.join("season1").join(IGNORE_FILE_NAME), "extras\n").unwrap();
+
+        let names = |result: ScanResult| {
+            let mut names: Vec<String> = result
+                .files
+                .iter()
+                .map(|f| {
+                    f.strip_prefix(&root)
+                        .unwrap()
+                        .to_string_lossy()
+                        .into_owned()
+                })
+                .collect();
+            names.sort();
+            names
+        };
+
+        let mut options = ScanOptions::default();
+        assert_eq!(
+            names(get_files_recursively(&root, &options)),
+            vec!["a.mkv", "b.MKV", "season1/d.mka", "season1/sample.mkv"]
+        );
+
+        options.exclude = build_globset(&["*sample*"]).unwrap();
+        options.max_depth = Some(1);
+        assert_eq!(
+            names(get_files_recursively(&root, &options)),
+            vec!["a.mkv", "b.MKV"]
+        );
+
+        options.max_depth = None;
+        options.regex = Some(Regex::new(r"\.mka$").unwrap());
+        assert_eq!(
+            names(get_files_recursively(&root, &options)),
+            vec!["season1/d.mka"]
+        );
+
+        let mut ignore_files = IgnoreFiles::default();
+        assert!(ignore_files.is_path_ignored(&root, &root.join("season1/extras/e.mkv")));
+        assert!(!ignore_files.is_path_ignored(&root, &root.join("season1/d.mka")));
+        assert!(!ignore_files.is_path_ignored(&root, &root.join("extras/e.mkv")));
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_read_file_list() {
+        let list = read_file_list(&b"a.mkv\0dir with space/b.mkv\0"[..]).unwrap();
+        assert_eq!(
+            list,
+            vec![
+                PathBuf::from("a.mkv"),
+                PathBuf::from("dir with space/b.mkv")
+            ]
+        );
+        let list = read_file_list(&b"a.mkv\nb.mkv\n"[..]).unwrap();
+        assert_eq!(list, vec![PathBuf::from("a.mkv"), PathBuf::from("b.mkv")]);
+    }
+}
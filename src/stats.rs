@@ -0,0 +1,223 @@
+use crate::file::{File, TrackType};
+use crate::group::Group;
+use itertools::Itertools;
+
+/// Number of groups shown in the list of the largest groups.
+const NUM_LARGEST_GROUPS: usize = 5;
+
+#[derive(Clone, Debug)]
+pub struct GroupSummary {
+    pub track_type: TrackType,
+    pub index: usize,
+    pub languages: String,
+    pub num_files: usize,
+}
+
+/// Statistics over all loaded files.
+#[derive(Clone, Debug, Default)]
+pub struct LibraryStats {
+    pub num_files: usize,
+    pub scan_errors: usize,
+    pub total_size: u64,
+    pub audio_languages: Vec<(String, usize)>,
+    pub subtitle_languages: Vec<(String, usize)>,
+    pub without_default_audio: usize,
+    pub multiple_default_subs: usize,
+    pub largest_groups: Vec<GroupSummary>,
+}
+
+fn language_distribution(files: &[File], track_type: TrackType) -> Vec<(String, usize)> {
+    files
+        .iter()
+        .flat_map(|file| file.tracks(track_type))
+        .map(|track| track.language.clone().unwrap_or_else(|| "und".to_owned()))
+        .counts()
+        .into_iter()
+        .sorted_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)))
+        .collect()
+}
+
+fn summarize_groups(groups: &[Group], track_type: TrackType) -> Vec<GroupSummary> {
+    groups
+        .iter()
+        .enumerate()
+        .map(|(index, group)| GroupSummary {
+            track_type,
+            index,
+            languages: group
+                .key
+                .iter()
+                .map(|key| key.language.as_deref().unwrap_or("und"))
+                .join(", "),
+            num_files: group.files.len(),
+        })
+        .collect()
+}
+
+impl LibraryStats {
+    /// The groups are numbered in the given order, which should be the order they are
+    /// listed in.
+    pub fn from_files(
+        files: &[File],
+        scan_errors: usize,
+        groups_audio: &[Group],
+        groups_subs: &[Group],
+    ) -> Self {
+        let total_size = files
+            .iter()
+            .filter_map(|file| std::fs::metadata(&file.file_name).ok())
+            .map(|metadata| metadata.len())
+            .sum();
+        let without_default_audio = files
+            .iter()
+            .filter(|file| !file.audio_tracks.is_empty())
+            .filter(|file| !file.audio_tracks.iter().any(|track| track.default))
+            .count();
+        let multiple_default_subs = files
+            .iter()
+            .filter(|file| {
+                file.subtitle_tracks
+                    .iter()
+                    .filter(|track| track.default)
+                    .count()
+                    > 1
+            })
+            .count();
+
+        let mut largest_groups = summarize_groups(groups_audio, TrackType::Audio);
+        largest_groups.extend(summarize_groups(groups_subs, TrackType::Subtitles));
+        largest_groups.sort_by_key(|group| std::cmp::Reverse(group.num_files));
+        largest_groups.truncate(NUM_LARGEST_GROUPS);
+
+        LibraryStats {
+            num_files: files.len(),
+            scan_errors,
+            total_size,
+            audio_languages: language_distribution(files, TrackType::Audio),
+            subtitle_languages: language_distribution(files, TrackType::Subtitles),
+            without_default_audio,
+            multiple_default_subs,
+            largest_groups,
+        }
+    }
+}
+
+pub fn format_size(size: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = size as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", size, UNITS[0])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::group::{groupby, key_audlang_audname, key_sublang_subname};
+
+    /// A file with audio and subtitle tracks given as language and default flag.
+    fn file(file_name: &str, audio: &[(&str, bool)], subs: &[(&str, bool)]) -> File {
+        let tracks = audio
+            .iter()
+            .map(|track| ("audio", track))
+            .chain(subs.iter().map(|track| ("subtitles", track)))
+            .enumerate()
+            .map(|(id, (track_type, (language, default)))| {
+                format!(
+                    r#"{{"id": {}, "type": "{}", "properties": {{"language": "{}",
+                        "default_track": {}, "forced_track": false, "enabled_track": true}}}}"#,
+                    id, track_type, language, default
+                )
+            })
+            .join(",");
+        File::from_json_str(&format!(
+            r#"{{"file_name": "{}", "tracks": [{}]}}"#,
+            file_name, tracks
+        ))
+        .unwrap()
+    }
+
+    fn files() -> Vec<File> {
+        vec![
+            file(
+                "a.mkv",
+                &[("jpn", false), ("eng", false)],
+                &[("eng", true), ("eng", true)],
+            ),
+            file("b.mkv", &[("jpn", true)], &[("und", false)]),
+            file("c.mkv", &[], &[]),
+            file("d.mkv", &[("jpn", true)], &[("und", false)]),
+        ]
+    }
+
+    #[test]
+    fn test_default_tracks() {
+        let files = files();
+        let stats = LibraryStats::from_files(&files, 2, &[], &[]);
+        assert_eq!(stats.num_files, 4);
+        assert_eq!(stats.scan_errors, 2);
+        // Files without audio tracks are not counted
+        assert_eq!(stats.without_default_audio, 1);
+        assert_eq!(stats.multiple_default_subs, 1);
+    }
+
+    #[test]
+    fn test_language_distribution() {
+        let files = files();
+        let stats = LibraryStats::from_files(&files, 0, &[], &[]);
+        let counts = |languages: &[(String, usize)]| {
+            languages
+                .iter()
+                .map(|(language, count)| (language.clone(), *count))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            counts(&stats.audio_languages),
+            [("jpn".to_owned(), 3), ("eng".to_owned(), 1)]
+        );
+        assert_eq!(
+            counts(&stats.subtitle_languages),
+            [("eng".to_owned(), 2), ("und".to_owned(), 2)]
+        );
+    }
+
+    #[test]
+    fn test_largest_groups() {
+        let files = files();
+        let groups_audio = groupby(&files, key_audlang_audname);
+        let groups_subs = groupby(&files, key_sublang_subname);
+        let stats = LibraryStats::from_files(&files, 0, &groups_audio, &groups_subs);
+        let num_files: Vec<usize> = stats
+            .largest_groups
+            .iter()
+            .map(|group| group.num_files)
+            .collect();
+        assert_eq!(num_files, [2, 2, 1, 1, 1]);
+
+        // Equal sizes keep the audio groups first, numbers refer to the given order
+        let first = &stats.largest_groups[0];
+        assert!(matches!(first.track_type, TrackType::Audio));
+        assert_eq!(first.languages, "jpn");
+        assert_eq!(groups_audio[first.index].files.len(), 2);
+        let second = &stats.largest_groups[1];
+        assert!(matches!(second.track_type, TrackType::Subtitles));
+        assert_eq!(second.languages, "und");
+        assert_eq!(groups_subs[second.index].files.len(), 2);
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(0), "0 B");
+        assert_eq!(format_size(1023), "1023 B");
+        assert_eq!(format_size(1024), "1.0 KiB");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(1024 * 1024 * 1024), "1.0 GiB");
+    }
+}
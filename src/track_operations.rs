@@ -7,16 +7,173 @@ pub(crate) enum TrackOperation<'a> {
     SetForced(bool),
     SetDefault(bool),
     SetEnabled(bool),
+    SetHearingImpaired(bool),
+    SetCommentary(bool),
     SetDefaultExclusive(bool),
     SetTitle(Option<&'a str>),
     SetLang(Option<&'a str>),
 }
 
+/// Operations that act on the whole file instead of a single track.
+#[allow(clippy::enum_variant_names)]
+#[derive(Clone, PartialEq, Debug)]
+pub(crate) enum FileOperation {
+    AddStatisticsTags,
+    DeleteStatisticsTags,
+    /// Path of the XML file with the tags.
+    SetGlobalTags(String),
+    ClearGlobalTags,
+}
+
+impl FileOperation {
+    pub(crate) fn description(&self) -> String {
+        match self {
+            FileOperation::AddStatisticsTags => "regenerate statistics tags".to_owned(),
+            FileOperation::DeleteStatisticsTags => "delete statistics tags".to_owned(),
+            FileOperation::SetGlobalTags(path) => format!("set global tags from {}", path),
+            FileOperation::ClearGlobalTags => "clear global tags".to_owned(),
+        }
+    }
+
+    /// Returns true if both operations cannot be applied in the same command.
+    pub(crate) fn conflicts_with(&self, other: &FileOperation) -> bool {
+        use FileOperation::*;
+        matches!(
+            (self, other),
+            (AddStatisticsTags, DeleteStatisticsTags)
+                | (DeleteStatisticsTags, AddStatisticsTags)
+                | (SetGlobalTags(_), ClearGlobalTags)
+                | (ClearGlobalTags, SetGlobalTags(_))
+                | (SetGlobalTags(_), SetGlobalTags(_))
+        )
+    }
+}
+
+/// Operations on the segment information of a file.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub(crate) enum SegmentOperation<'a> {
+    SetTitle(Option<&'a str>),
+}
+
+/// Operations on the tracks of one type, track numbers are indexes into the tracks of that type.
+#[derive(Clone)]
 pub(crate) struct TrackOperations<'a> {
     track_type: TrackType,
     cmds: Vec<(i64, TrackOperation<'a>)>,
 }
 
+/// All edits of one file: track operations of all track types, segment information and
+/// file operations. They are applied with a single mkvpropedit call, so the file header
+/// is only rewritten once.
+#[derive(Clone, Default)]
+pub(crate) struct EditPlan<'a> {
+    track_ops: Vec<TrackOperations<'a>>,
+    segment_cmds: Vec<SegmentOperation<'a>>,
+    file_cmds: Vec<FileOperation>,
+}
+
+impl<'a> EditPlan<'a> {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn empty(&self) -> bool {
+        self.track_ops.iter().all(TrackOperations::empty)
+            && self.segment_cmds.is_empty()
+            && self.file_cmds.is_empty()
+    }
+
+    fn track_ops_mut(&mut self, track_type: TrackType) -> &mut TrackOperations<'a> {
+        let pos = match self
+            .track_ops
+            .iter()
+            .position(|ops| ops.track_type == track_type)
+        {
+            Some(pos) => pos,
+            None => {
+                self.track_ops.push(TrackOperations::new(track_type));
+                self.track_ops.len() - 1
+            }
+        };
+        &mut self.track_ops[pos]
+    }
+
+    pub(crate) fn add(
+        &mut self,
+        track_type: TrackType,
+        track_no: i64,
+        track_command: TrackOperation<'a>,
+    ) {
+        self.track_ops_mut(track_type).add(track_no, track_command);
+    }
+
+    /// Adds all operations of `track_ops` after the operations already planned.
+    pub(crate) fn add_track_ops(&mut self, track_ops: &TrackOperations<'a>) {
+        self.track_ops_mut(track_ops.track_type)
+            .cmds
+            .extend(track_ops.cmds.iter().copied());
+    }
+
+    pub(crate) fn add_segment_op(&mut self, segment_command: SegmentOperation<'a>) {
+        self.segment_cmds.push(segment_command);
+    }
+
+    pub(crate) fn add_file_op(&mut self, file_command: FileOperation) {
+        self.file_cmds.push(file_command);
+    }
+
+    pub(crate) fn generate_command(&self, file: &File) -> Command {
+        let mut command = Command::new("mkvpropedit");
+        for track_ops in &self.track_ops {
+            command.arguments.extend(track_ops.generate_arguments(file));
+        }
+        for segment_cmd in &self.segment_cmds {
+            EditPlan::segment_operation(&mut command.arguments, segment_cmd);
+        }
+        for file_cmd in &self.file_cmds {
+            EditPlan::file_operation(&mut command.arguments, file_cmd);
+        }
+        command.arguments.push(file.file_name.clone());
+        command
+    }
+
+    fn segment_operation(arguments: &mut Vec<String>, segment_cmd: &SegmentOperation) {
+        match segment_cmd {
+            SegmentOperation::SetTitle(title) => {
+                arguments.push("--edit".to_owned());
+                arguments.push("info".to_owned());
+                if let Some(title) = title {
+                    arguments.push("--set".to_owned());
+                    arguments.push(format!("title={}", title));
+                } else {
+                    arguments.push("--delete".to_owned());
+                    arguments.push("title".to_owned());
+                }
+            }
+        }
+    }
+
+    fn file_operation(arguments: &mut Vec<String>, file_cmd: &FileOperation) {
+        match file_cmd {
+            FileOperation::AddStatisticsTags => {
+                arguments.push("--add-track-statistics-tags".to_owned());
+            }
+            FileOperation::DeleteStatisticsTags => {
+                arguments.push("--delete-track-statistics-tags".to_owned());
+            }
+            FileOperation::SetGlobalTags(path) => {
+                arguments.push("--tags".to_owned());
+                arguments.push(format!("global:{}", path));
+            }
+            FileOperation::ClearGlobalTags => {
+                // An empty file name removes all global tags
+                arguments.push("--tags".to_owned());
+                arguments.push("global:".to_owned());
+            }
+        }
+    }
+}
+
 impl<'a> TrackOperations<'a> {
     pub(crate) fn new(track_type: TrackType) -> Self {
         TrackOperations {
@@ -26,28 +183,16 @@ impl<'a> TrackOperations<'a> {
     }
 
     pub(crate) fn empty(&self) -> bool {
-        !self.cmds.is_empty()
+        self.cmds.is_empty()
     }
 
     pub(crate) fn add(&mut self, track_no: i64, track_command: TrackOperation<'a>) {
         self.cmds.push((track_no, track_command));
     }
 
-    pub(crate) fn generate_command(&self, file: &File) -> Command {
-        let mut command = Command::new("mkvpropedit");
-        let mut arguments = self.generate_arguments(file);
-        arguments.push(file.file_name.clone());
-        command.arguments.extend(arguments);
-        command
-    }
-
     fn generate_arguments(&self, file: &File) -> Vec<String> {
         let mut arguments = Vec::<String>::new();
-        let tracks = match self.track_type {
-            TrackType::Subtitles => &file.subtitle_tracks,
-            TrackType::Audio => &file.audio_tracks,
-            TrackType::Video => &file.video_tracks,
-        };
+        let tracks = file.tracks(self.track_type);
         let get_track_id = |track_no| tracks.get(track_no as usize).unwrap().id;
 
         for cmd in &self.cmds {
@@ -77,6 +222,22 @@ impl<'a> TrackOperations<'a> {
                         val,
                     );
                 }
+                TrackOperation::SetHearingImpaired(val) => {
+                    TrackOperations::set_flag(
+                        &mut arguments,
+                        get_track_id(track_no),
+                        Flag::HearingImpaired,
+                        val,
+                    );
+                }
+                TrackOperation::SetCommentary(val) => {
+                    TrackOperations::set_flag(
+                        &mut arguments,
+                        get_track_id(track_no),
+                        Flag::Commentary,
+                        val,
+                    );
+                }
                 TrackOperation::SetDefaultExclusive(_) => {
                     // TODO: remove bool completely?
                     for track in tracks.iter() {
@@ -131,8 +292,89 @@ impl<'a> TrackOperations<'a> {
             Flag::Default => "flag-default",
             Flag::Forced => "flag-forced",
             Flag::Enabled => "flag-enabled",
+            Flag::HearingImpaired => "flag-hearing-impaired",
+            Flag::Commentary => "flag-commentary",
         };
         let value = if value { "1" } else { "0" };
         arguments.push(format!("{}={}", flag_str, value));
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edit_plan() {
+        let file = File::from_json_str(
+            r#"{
+                "file_name": "movie.mkv",
+                "tracks": [
+                    {"id": 0, "type": "audio", "properties": {"language": "jpn",
+                        "default_track": true, "forced_track": false, "enabled_track": true}},
+                    {"id": 1, "type": "subtitles", "properties": {"language": "eng",
+                        "default_track": false, "forced_track": false, "enabled_track": true}}
+                ]
+            }"#,
+        )
+        .unwrap();
+        let mut plan = EditPlan::new();
+        assert!(plan.empty());
+        plan.add(TrackType::Subtitles, 0, TrackOperation::SetForced(true));
+        plan.add(
+            TrackType::Audio,
+            0,
+            TrackOperation::SetTitle(Some("Japanese")),
+        );
+        plan.add_segment_op(SegmentOperation::SetTitle(None));
+        let command = plan.generate_command(&file);
+        assert_eq!(
+            command.arguments,
+            vec![
+                "--edit",
+                "track:@2",
+                "--set",
+                "flag-forced=1",
+                "--edit",
+                "track:@1",
+                "--set",
+                "name=Japanese",
+                "--edit",
+                "info",
+                "--delete",
+                "title",
+                "movie.mkv",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_file_operation() {
+        let arguments = |file_cmd: FileOperation| {
+            let mut arguments = Vec::new();
+            EditPlan::file_operation(&mut arguments, &file_cmd);
+            arguments
+        };
+        assert_eq!(
+            arguments(FileOperation::AddStatisticsTags),
+            vec!["--add-track-statistics-tags"]
+        );
+        assert_eq!(
+            arguments(FileOperation::DeleteStatisticsTags),
+            vec!["--delete-track-statistics-tags"]
+        );
+        assert_eq!(
+            arguments(FileOperation::SetGlobalTags("tags.xml".to_owned())),
+            vec!["--tags", "global:tags.xml"]
+        );
+        assert_eq!(
+            arguments(FileOperation::ClearGlobalTags),
+            vec!["--tags", "global:"]
+        );
+        assert!(
+            FileOperation::ClearGlobalTags
+                .conflicts_with(&FileOperation::SetGlobalTags("tags.xml".to_owned()))
+        );
+        assert!(!FileOperation::ClearGlobalTags.conflicts_with(&FileOperation::AddStatisticsTags));
+    }
+}
@@ -3,12 +3,15 @@ use crate::group::Group;
 use crate::ui::Action;
 use crate::ui::ActiveWidget;
 use crate::ui::KeyPressConsumer;
+use crate::ui::item_at;
+use crate::ui::keymap::{KeyAction, KeyContext, Keymap};
 use crate::ui::selectable_state::SelectableState;
-use crossterm::event::KeyCode;
+use crate::ui::theme::Theme;
+use crossterm::event::{MouseButton, MouseEvent, MouseEventKind};
 use ratatui::{
     Frame,
     layout::Rect,
-    style::{Color, Modifier, Style},
+    style::Style,
     text::{Line, Span},
     widgets::{Block, BorderType, Borders, List, ListItem, ListState},
 };
@@ -17,31 +20,49 @@ use ratatui::{
 pub(crate) struct GroupFilesListWidget {
     list_state: ListState,
     file_names: Vec<String>,
+    /// Where the list was drawn last, used for mouse clicks.
+    area: Rect,
 }
 
 impl KeyPressConsumer for GroupFilesListWidget {
-    fn process_key(&mut self, key_code: crossterm::event::KeyCode) -> Action {
-        match key_code {
-            KeyCode::Up | KeyCode::Char('k') => {
+    fn process_key(&mut self, key_event: crossterm::event::KeyEvent, keymap: &Keymap) -> Action {
+        match keymap.action(key_event, &[KeyContext::Navigation]) {
+            Some(KeyAction::Up) => {
                 if let Some(down_res) = self.navigate_up()
                     && !down_res
                 {
                     return Action::NavigateBackward(ActiveWidget::Files);
                 }
             }
-            KeyCode::Down | KeyCode::Char('j') => {
+            Some(KeyAction::Down) => {
                 self.navigate_down();
             }
-            KeyCode::Esc => {
+            Some(KeyAction::Back) => {
                 return Action::NavigateBackward(ActiveWidget::Files);
             }
-            KeyCode::Left | KeyCode::Char('h') => {
+            Some(KeyAction::Left) => {
                 return Action::NavigateBackward(ActiveWidget::Files);
             }
             _ => {}
         }
         Action::Pass
     }
+
+    fn process_mouse(&mut self, mouse_event: MouseEvent) -> Action {
+        let Some(pos) = item_at(self.area, mouse_event, 0, self.list_state.offset()) else {
+            return Action::Pass;
+        };
+        match mouse_event.kind {
+            MouseEventKind::Down(MouseButton::Left) if pos < self.file_names.len() => {
+                self.select(Some(pos));
+                return Action::Focus(ActiveWidget::Files);
+            }
+            MouseEventKind::ScrollUp => self.scroll(true),
+            MouseEventKind::ScrollDown => self.scroll(false),
+            _ => {}
+        }
+        Action::Pass
+    }
 }
 
 impl SelectableState for GroupFilesListWidget {
@@ -70,6 +91,24 @@ impl GroupFilesListWidget {
         ActiveWidget::Files
     }
 
+    /// Moves the selection, without a selection only the visible part of the list moves.
+    fn scroll(&mut self, up: bool) {
+        let moved = if up {
+            self.navigate_up()
+        } else {
+            self.navigate_down()
+        };
+        if moved.is_none() {
+            let last = self.file_names.len().saturating_sub(1);
+            let offset = self.list_state.offset_mut();
+            *offset = if up {
+                offset.saturating_sub(1)
+            } else {
+                (*offset + 1).min(last)
+            };
+        }
+    }
+
     pub(crate) fn from_group(group: Option<&Group>) -> Self {
         let mut new = Self::default();
         new.set_filenames(group);
@@ -81,11 +120,18 @@ impl GroupFilesListWidget {
         self.list_state = ListState::default();
         if let Some(group) = group {
             self.file_names
-                .extend(group.files.iter().map(|file| file.file_name.clone()));
+                .extend(group.files.iter().map(|file| file.display_name()));
         }
     }
 
-    pub(crate) fn render(&mut self, frame: &mut Frame, area: Rect, focus: FocusState) {
+    pub(crate) fn render(
+        &mut self,
+        frame: &mut Frame,
+        area: Rect,
+        focus: FocusState,
+        theme: &Theme,
+    ) {
+        self.area = area;
         // Group files
         let group_files_items: Vec<_> = self
             .file_names
@@ -98,23 +144,18 @@ impl GroupFilesListWidget {
             })
             .collect();
 
-        let border_style = Style::default().fg(focus.border_color());
+        let border_style = Style::default().fg(focus.border_color(theme));
 
         let group_files = List::new(group_files_items)
             .block(
                 Block::default()
                     .borders(Borders::ALL)
-                    .style(Style::default().fg(focus.text_color()))
+                    .style(Style::default().fg(focus.text_color(theme)))
                     .title("Files")
                     .border_type(BorderType::Plain)
                     .border_style(border_style),
             )
-            .highlight_style(
-                Style::default()
-                    .bg(focus.sel_color())
-                    .fg(Color::Black)
-                    .add_modifier(Modifier::BOLD),
-            );
+            .highlight_style(focus.selection(theme));
         frame.render_stateful_widget(group_files, area, &mut self.list_state);
     }
 }
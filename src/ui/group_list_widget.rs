@@ -1,37 +1,95 @@
 use super::FocusState;
+use crate::group::{FileSort, Group, GroupSort};
 use crate::ui::Action;
 use crate::ui::ActiveWidget;
 use crate::ui::KeyPressConsumer;
+use crate::ui::item_at;
+use crate::ui::keymap::{KeyAction, KeyContext, Keymap};
 use crate::ui::selectable_state::SelectableState;
-use crossterm::event::KeyCode;
+use crate::ui::theme::Theme;
+use crossterm::event::{KeyCode, MouseButton, MouseEvent, MouseEventKind};
 use ratatui::{
     Frame,
     layout::Rect,
-    style::{Color, Modifier, Style},
+    style::Style,
     text::{Line, Span},
     widgets::{Block, BorderType, Borders, List, ListItem, ListState},
 };
 
 #[derive(Clone, Default)]
 pub(crate) struct GroupListWidget {
+    /// Selects a position in `visible`, not a group index.
     list_state: ListState,
-    num_groups: usize,
+    labels: Vec<String>,
+    /// Lower case file names, track names and languages of each group.
+    search_texts: Vec<String>,
+    /// Indexes of the groups matching the search query.
+    visible: Vec<usize>,
+    query: String,
+    /// The query is being typed, all keys go to the search field.
+    searching: bool,
+    /// Groups with pending changes that were not applied yet.
+    modified: Vec<bool>,
+    group_sort: GroupSort,
+    file_sort: FileSort,
+    /// Where the list was drawn last, used for mouse clicks.
+    area: Rect,
 }
 
 impl KeyPressConsumer for GroupListWidget {
-    fn process_key(&mut self, key_code: crossterm::event::KeyCode) -> Action {
-        match key_code {
-            KeyCode::Up | KeyCode::Char('k') => {
+    fn process_key(&mut self, key_event: crossterm::event::KeyEvent, keymap: &Keymap) -> Action {
+        if self.searching {
+            return self.process_search_key(key_event.code);
+        }
+        match keymap.action(key_event, &[KeyContext::Navigation, KeyContext::GroupList]) {
+            Some(KeyAction::Up) => {
                 self.navigate_up();
                 return Action::LoadGroup;
             }
-            KeyCode::Down | KeyCode::Char('j') => {
+            Some(KeyAction::Down) => {
                 self.navigate_down();
                 return Action::LoadGroup;
             }
-            KeyCode::Right | KeyCode::Char('l') => {
+            Some(KeyAction::Right) => {
                 return Action::NavigateForward(ActiveWidget::Groups);
             }
+            Some(KeyAction::Search) => {
+                self.searching = true;
+            }
+            Some(KeyAction::SearchNext) if !self.query.is_empty() => {
+                self.jump(true);
+                return Action::LoadGroup;
+            }
+            Some(KeyAction::SearchPrev) if !self.query.is_empty() => {
+                self.jump(false);
+                return Action::LoadGroup;
+            }
+            Some(KeyAction::Back) if !self.query.is_empty() => {
+                self.set_query(String::new());
+                return Action::LoadGroup;
+            }
+            _ => {}
+        }
+        Action::Pass
+    }
+
+    fn process_mouse(&mut self, mouse_event: MouseEvent) -> Action {
+        let Some(pos) = item_at(self.area, mouse_event, 0, self.list_state.offset()) else {
+            return Action::Pass;
+        };
+        match mouse_event.kind {
+            MouseEventKind::Down(MouseButton::Left) if pos < self.visible.len() => {
+                self.select(Some(pos));
+                return Action::Focus(ActiveWidget::Groups);
+            }
+            MouseEventKind::ScrollUp => {
+                self.navigate_up();
+                return Action::LoadGroup;
+            }
+            MouseEventKind::ScrollDown => {
+                self.navigate_down();
+                return Action::LoadGroup;
+            }
             _ => {}
         }
         Action::Pass
@@ -48,53 +106,210 @@ impl SelectableState for GroupListWidget {
     }
 
     fn length(&self) -> usize {
-        self.num_groups
+        self.visible.len()
+    }
+}
+
+fn group_label(idx: usize, group: &Group) -> String {
+    let mut label = format!(
+        "#{} {} ({} files)",
+        idx,
+        group.languages().join(","),
+        group.files.len()
+    );
+    if let Some(dir) = group.common_directory()
+        && let Some(dir_name) = dir.file_name()
+    {
+        label.push(' ');
+        label.push_str(&dir_name.to_string_lossy());
+    }
+    label
+}
+
+fn group_search_text(group: &Group) -> String {
+    let mut text = String::new();
+    for key in &group.key {
+        text.push_str(key.language.as_deref().unwrap_or("und"));
+        text.push('\n');
+        if let Some(ref name) = key.name {
+            text.push_str(name);
+            text.push('\n');
+        }
+    }
+    for file in &group.files {
+        text.push_str(&file.file_name);
+        text.push('\n');
     }
+    text.to_lowercase()
 }
 
 impl GroupListWidget {
-    pub(crate) fn new(num_groups: usize) -> Self {
+    pub(crate) fn new(groups: &[Group]) -> Self {
         let mut new = Self {
-            num_groups,
+            labels: groups
+                .iter()
+                .enumerate()
+                .map(|(idx, group)| group_label(idx, group))
+                .collect(),
+            search_texts: groups.iter().map(group_search_text).collect(),
+            visible: (0..groups.len()).collect(),
             ..Self::default()
         };
-        if num_groups != 0 {
+        if !groups.is_empty() {
             new.select(Some(0));
         }
         new
     }
 
+    pub(crate) fn set_modified(&mut self, modified: Vec<bool>) {
+        self.modified = modified;
+    }
+
+    pub(crate) fn set_sort(&mut self, group_sort: GroupSort, file_sort: FileSort) {
+        self.group_sort = group_sort;
+        self.file_sort = file_sort;
+    }
+
+    /// Whether keys are typed into the search field.
+    pub(crate) fn searching(&self) -> bool {
+        self.searching
+    }
+
+    pub(crate) fn query(&self) -> &str {
+        &self.query
+    }
+
+    /// Filters the groups with the query and selects the group if it matches, used to keep
+    /// the state of the list when the groups are rebuilt.
+    pub(crate) fn restore(&mut self, query: String, selected_group: Option<usize>) {
+        if let Some(group) = selected_group {
+            // All groups are visible before the query is set
+            self.select(Some(group));
+        }
+        self.set_query(query);
+    }
+
+    /// Index of the selected group.
+    pub(crate) fn selected_group(&self) -> Option<usize> {
+        self.selected()
+            .and_then(|pos| self.visible.get(pos).copied())
+    }
+
+    fn process_search_key(&mut self, key_code: KeyCode) -> Action {
+        match key_code {
+            KeyCode::Enter => {
+                self.searching = false;
+                return Action::Pass;
+            }
+            KeyCode::Esc => {
+                self.searching = false;
+                self.set_query(String::new());
+            }
+            KeyCode::Backspace => {
+                let mut query = self.query.clone();
+                query.pop();
+                self.set_query(query);
+            }
+            KeyCode::Char(c) => {
+                let mut query = self.query.clone();
+                query.push(c);
+                self.set_query(query);
+            }
+            _ => return Action::Pass,
+        }
+        Action::LoadGroup
+    }
+
+    /// Filters the groups, the selected group stays selected if it still matches.
+    fn set_query(&mut self, query: String) {
+        let selected_group = self.selected_group();
+        self.query = query;
+        let query = self.query.to_lowercase();
+        self.visible = self
+            .search_texts
+            .iter()
+            .enumerate()
+            .filter(|(_, text)| text.contains(&query))
+            .map(|(idx, _)| idx)
+            .collect();
+        let pos = selected_group
+            .and_then(|group| self.visible.iter().position(|idx| *idx == group))
+            .or(if self.visible.is_empty() {
+                None
+            } else {
+                Some(0)
+            });
+        self.select(pos);
+    }
+
+    /// Selects the next or previous match, wrapping around at the end of the list.
+    fn jump(&mut self, forward: bool) {
+        let len = self.visible.len();
+        if len == 0 {
+            return;
+        }
+        let pos = match self.selected() {
+            Some(pos) if forward => (pos + 1) % len,
+            Some(pos) => (pos + len - 1) % len,
+            None => 0,
+        };
+        self.select(Some(pos));
+    }
+
     pub(crate) fn widget_type() -> ActiveWidget {
         ActiveWidget::Groups
     }
 
-    //TODO: remove groups, maybe keep a copy
-    pub(crate) fn render(&mut self, frame: &mut Frame, area: Rect, focus: FocusState) {
-        let border_style = Style::default().fg(focus.border_color());
+    pub(crate) fn render(
+        &mut self,
+        frame: &mut Frame,
+        area: Rect,
+        focus: FocusState,
+        theme: &Theme,
+    ) {
+        self.area = area;
+        let border_style = Style::default().fg(focus.border_color(theme));
+        let sort = format!(
+            "Groups by {}, files by {}",
+            self.group_sort.id(),
+            self.file_sort.id()
+        );
+        let title = if self.searching {
+            format!("{} /{}_", sort, self.query)
+        } else if !self.query.is_empty() {
+            format!("{} /{} ({} matches)", sort, self.query, self.visible.len())
+        } else {
+            sort
+        };
         let groupnames_block = Block::default()
             .borders(Borders::ALL)
-            .style(Style::default().fg(focus.text_color()))
-            .title("Groups")
+            .style(Style::default().fg(focus.text_color(theme)))
+            .title(title)
             .border_type(BorderType::Plain)
             .border_style(border_style);
 
-        let groupnames_items: Vec<_> = (0..self.num_groups)
+        let groupnames_items: Vec<_> = self
+            .visible
+            .iter()
             .map(|idx| {
-                ListItem::new(Line::from(vec![Span::styled(
-                    format!("Group #{}", idx),
-                    Style::default(),
-                )]))
+                let label = &self.labels[*idx];
+                if self.modified.get(*idx).copied().unwrap_or(false) {
+                    ListItem::new(Line::from(vec![Span::styled(
+                        format!("{} *", label),
+                        Style::default().fg(theme.modified),
+                    )]))
+                } else {
+                    ListItem::new(Line::from(vec![Span::styled(
+                        label.clone(),
+                        Style::default(),
+                    )]))
+                }
             })
             .collect();
 
         let groupnames_list = List::new(groupnames_items)
             .block(groupnames_block)
-            .highlight_style(
-                Style::default()
-                    .bg(focus.sel_color())
-                    .fg(Color::Black)
-                    .add_modifier(Modifier::BOLD),
-            );
+            .highlight_style(focus.selection(theme));
         frame.render_stateful_widget(groupnames_list, area, &mut self.list_state);
     }
 }
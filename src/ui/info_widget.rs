@@ -0,0 +1,139 @@
+use crate::file::TrackType;
+use crate::stats::{LibraryStats, format_size};
+use crate::ui::theme::Theme;
+use ratatui::{
+    Frame,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, BorderType, Borders, Paragraph},
+};
+
+/// Dashboard with statistics about the loaded library.
+#[derive(Clone, Default)]
+pub(crate) struct InfoWidget {
+    stats: LibraryStats,
+}
+
+fn block<'a>(title: &'a str, theme: &Theme) -> Block<'a> {
+    Block::default()
+        .borders(Borders::ALL)
+        .style(Style::default().fg(theme.text))
+        .title(title)
+        .border_type(BorderType::Plain)
+}
+
+fn stat_line(label: &str, value: String, warn: bool, theme: &Theme) -> Line<'static> {
+    let value_style = if warn {
+        theme.warning_style().add_modifier(Modifier::BOLD)
+    } else {
+        Style::default().add_modifier(Modifier::BOLD)
+    };
+    Line::from(vec![
+        Span::raw(format!("{:<28}", label)),
+        Span::styled(value, value_style),
+    ])
+}
+
+fn language_lines(languages: &[(String, usize)]) -> Vec<Line<'static>> {
+    languages
+        .iter()
+        .map(|(lang, count)| Line::from(format!("{:<6}{:>6}", lang, count)))
+        .collect()
+}
+
+impl InfoWidget {
+    pub(crate) fn new(stats: LibraryStats) -> Self {
+        Self { stats }
+    }
+
+    pub(crate) fn render(&self, frame: &mut Frame, area: Rect, theme: &Theme) {
+        let vert_split = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(10), Constraint::Min(3)].as_ref())
+            .split(area);
+        let top_split = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+            .split(vert_split[0]);
+        let bottom_split = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+            .split(vert_split[1]);
+
+        let stats = &self.stats;
+        let summary = Paragraph::new(vec![
+            stat_line("Files", stats.num_files.to_string(), false, theme),
+            stat_line(
+                "Scan errors",
+                stats.scan_errors.to_string(),
+                stats.scan_errors > 0,
+                theme,
+            ),
+            stat_line("Total size", format_size(stats.total_size), false, theme),
+            stat_line(
+                "Files without default audio",
+                stats.without_default_audio.to_string(),
+                stats.without_default_audio > 0,
+                theme,
+            ),
+            stat_line(
+                "Files with multiple def. subs",
+                stats.multiple_default_subs.to_string(),
+                stats.multiple_default_subs > 0,
+                theme,
+            ),
+        ])
+        .block(block("Library", theme));
+        frame.render_widget(summary, top_split[0]);
+
+        let help = Paragraph::new(vec![
+            Line::from(vec![Span::raw(
+                "Press 'S' to access Subtitle view, 'A' to access audio track view.",
+            )]),
+            Line::from(vec![Span::raw(
+                "Files are grouped by their track metadata (name, lang, flags).",
+            )]),
+            Line::from(vec![Span::raw(
+                "Changes are applied to all files in a group!",
+            )]),
+        ])
+        .block(block("mtxstuff", theme));
+        frame.render_widget(help, top_split[1]);
+
+        let lang_split = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+            .split(bottom_split[0]);
+        let audio_langs = Paragraph::new(language_lines(&stats.audio_languages))
+            .block(block("Audio languages", theme));
+        frame.render_widget(audio_langs, lang_split[0]);
+        let sub_langs = Paragraph::new(language_lines(&stats.subtitle_languages))
+            .block(block("Subtitle languages", theme));
+        frame.render_widget(sub_langs, lang_split[1]);
+
+        let group_lines: Vec<Line> = stats
+            .largest_groups
+            .iter()
+            .map(|group| {
+                let tab = match group.track_type {
+                    TrackType::Audio => "Audio",
+                    TrackType::Subtitles => "Subs",
+                    TrackType::Video => "Video",
+                };
+                Line::from(vec![
+                    Span::styled(
+                        format!("{:>5} files  ", group.num_files),
+                        Style::default().add_modifier(Modifier::BOLD),
+                    ),
+                    Span::raw(format!(
+                        "{} group #{}: {}",
+                        tab, group.index, group.languages
+                    )),
+                ])
+            })
+            .collect();
+        let groups = Paragraph::new(group_lines).block(block("Largest groups", theme));
+        frame.render_widget(groups, bottom_split[1]);
+    }
+}
@@ -0,0 +1,780 @@
+use anyhow::{Context, Result, anyhow, bail};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+/// Where a key binding applies, actions of different contexts may share keys.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum KeyContext {
+    Global,
+    /// Moving around in lists and tables.
+    Navigation,
+    GroupTab,
+    GroupList,
+    TrackTable,
+    Problems,
+    /// Keys shared by the popups. Text input always uses Enter, Esc and Backspace.
+    Popup,
+    CommandPreview,
+    Suggestions,
+    CommandLog,
+}
+
+impl KeyContext {
+    fn title(&self) -> &'static str {
+        match self {
+            KeyContext::Global => "Global",
+            KeyContext::Navigation => "Navigation",
+            KeyContext::GroupTab => "Groups",
+            KeyContext::GroupList => "Group list",
+            KeyContext::TrackTable => "Track table",
+            KeyContext::Problems => "Problems",
+            KeyContext::Popup => "Popups",
+            KeyContext::CommandPreview => "Command preview",
+            KeyContext::Suggestions => "Suggestions",
+            KeyContext::CommandLog => "Command log",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub(crate) enum KeyAction {
+    TabInfo,
+    TabSubs,
+    TabAudio,
+    TabProblems,
+    Quit,
+    Help,
+    Up,
+    Down,
+    Left,
+    Right,
+    Back,
+    GroupCommands,
+    ApplyPending,
+    Undo,
+    Redo,
+    RevertAll,
+    ChooseDefault,
+    Suggestions,
+    Rename,
+    SortGroups,
+    SortFiles,
+    Search,
+    SearchNext,
+    SearchPrev,
+    Edit,
+    Clear,
+    Visual,
+    ExclusiveDefault,
+    RevertCell,
+    RevertRow,
+    ToggleFix,
+    AcceptFile,
+    AcceptGroup,
+    ApplyFixes,
+    Confirm,
+    RunCommands,
+    SaveCommands,
+    StatisticsTags,
+    DeleteStatisticsTags,
+    GlobalTags,
+    ClearGlobalTags,
+    ToggleSuggestion,
+    FollowLog,
+}
+
+struct ActionInfo {
+    action: KeyAction,
+    id: &'static str,
+    context: KeyContext,
+    keys: &'static [&'static str],
+    description: &'static str,
+}
+
+const fn info(
+    action: KeyAction,
+    id: &'static str,
+    context: KeyContext,
+    keys: &'static [&'static str],
+    description: &'static str,
+) -> ActionInfo {
+    ActionInfo {
+        action,
+        id,
+        context,
+        keys,
+        description,
+    }
+}
+
+/// All actions with their default keys, the help lists them in this order.
+const ACTIONS: [ActionInfo; 43] = [
+    info(
+        KeyAction::TabInfo,
+        "tab-info",
+        KeyContext::Global,
+        &["i"],
+        "Library info",
+    ),
+    info(
+        KeyAction::TabSubs,
+        "tab-subs",
+        KeyContext::Global,
+        &["s"],
+        "Subtitle groups",
+    ),
+    info(
+        KeyAction::TabAudio,
+        "tab-audio",
+        KeyContext::Global,
+        &["a"],
+        "Audio groups",
+    ),
+    info(
+        KeyAction::TabProblems,
+        "tab-problems",
+        KeyContext::Global,
+        &["p"],
+        "Problems",
+    ),
+    info(KeyAction::Quit, "quit", KeyContext::Global, &["q"], "Quit"),
+    info(KeyAction::Help, "help", KeyContext::Global, &["?"], "Help"),
+    info(
+        KeyAction::Up,
+        "up",
+        KeyContext::Navigation,
+        &["Up", "k"],
+        "Move up",
+    ),
+    info(
+        KeyAction::Down,
+        "down",
+        KeyContext::Navigation,
+        &["Down", "j"],
+        "Move down",
+    ),
+    info(
+        KeyAction::Left,
+        "left",
+        KeyContext::Navigation,
+        &["Left", "h"],
+        "Move left",
+    ),
+    info(
+        KeyAction::Right,
+        "right",
+        KeyContext::Navigation,
+        &["Right", "l"],
+        "Move right",
+    ),
+    info(
+        KeyAction::Back,
+        "back",
+        KeyContext::Navigation,
+        &["Esc"],
+        "Back",
+    ),
+    info(
+        KeyAction::GroupCommands,
+        "group-commands",
+        KeyContext::GroupTab,
+        &["F2"],
+        "Apply group",
+    ),
+    info(
+        KeyAction::ApplyPending,
+        "apply-pending",
+        KeyContext::GroupTab,
+        &["F3"],
+        "Apply all pending",
+    ),
+    info(
+        KeyAction::Undo,
+        "undo",
+        KeyContext::GroupTab,
+        &["u"],
+        "Undo",
+    ),
+    info(
+        KeyAction::Redo,
+        "redo",
+        KeyContext::GroupTab,
+        &["ctrl-r"],
+        "Redo",
+    ),
+    info(
+        KeyAction::RevertAll,
+        "revert-all",
+        KeyContext::GroupTab,
+        &["U"],
+        "Revert group",
+    ),
+    info(
+        KeyAction::ChooseDefault,
+        "choose-default",
+        KeyContext::GroupTab,
+        &["d"],
+        "Choose default by policy",
+    ),
+    info(
+        KeyAction::Suggestions,
+        "suggestions",
+        KeyContext::GroupTab,
+        &["r"],
+        "Suggestions",
+    ),
+    info(
+        KeyAction::Rename,
+        "rename",
+        KeyContext::GroupTab,
+        &["R"],
+        "Rename by template",
+    ),
+    info(
+        KeyAction::SortGroups,
+        "sort-groups",
+        KeyContext::GroupTab,
+        &["S"],
+        "Sort groups",
+    ),
+    info(
+        KeyAction::SortFiles,
+        "sort-files",
+        KeyContext::GroupTab,
+        &["F"],
+        "Sort files",
+    ),
+    info(
+        KeyAction::Search,
+        "search",
+        KeyContext::GroupList,
+        &["/"],
+        "Search",
+    ),
+    info(
+        KeyAction::SearchNext,
+        "search-next",
+        KeyContext::GroupList,
+        &["n"],
+        "Next match",
+    ),
+    info(
+        KeyAction::SearchPrev,
+        "search-prev",
+        KeyContext::GroupList,
+        &["N"],
+        "Previous match",
+    ),
+    info(
+        KeyAction::Edit,
+        "edit",
+        KeyContext::TrackTable,
+        &["Enter"],
+        "Edit cell / toggle flag",
+    ),
+    info(
+        KeyAction::Clear,
+        "clear",
+        KeyContext::TrackTable,
+        &["Delete"],
+        "Unset language / name",
+    ),
+    info(
+        KeyAction::Visual,
+        "visual",
+        KeyContext::TrackTable,
+        &["v"],
+        "Visual selection",
+    ),
+    info(
+        KeyAction::ExclusiveDefault,
+        "exclusive-default",
+        KeyContext::TrackTable,
+        &["o"],
+        "Only default track",
+    ),
+    info(
+        KeyAction::RevertCell,
+        "revert-cell",
+        KeyContext::TrackTable,
+        &["x"],
+        "Revert cell",
+    ),
+    info(
+        KeyAction::RevertRow,
+        "revert-row",
+        KeyContext::TrackTable,
+        &["X"],
+        "Revert row",
+    ),
+    info(
+        KeyAction::ToggleFix,
+        "toggle-fix",
+        KeyContext::Problems,
+        &["Space"],
+        "Toggle fix",
+    ),
+    info(
+        KeyAction::AcceptFile,
+        "accept-file",
+        KeyContext::Problems,
+        &["f"],
+        "Accept file",
+    ),
+    info(
+        KeyAction::AcceptGroup,
+        "accept-group",
+        KeyContext::Problems,
+        &["g"],
+        "Accept group",
+    ),
+    info(
+        KeyAction::ApplyFixes,
+        "apply-fixes",
+        KeyContext::Problems,
+        &["F2"],
+        "Apply fixes",
+    ),
+    info(
+        KeyAction::Confirm,
+        "confirm",
+        KeyContext::Popup,
+        &["Enter"],
+        "Confirm / close",
+    ),
+    info(
+        KeyAction::RunCommands,
+        "run-commands",
+        KeyContext::CommandPreview,
+        &["F2"],
+        "Run commands",
+    ),
+    info(
+        KeyAction::SaveCommands,
+        "save-commands",
+        KeyContext::CommandPreview,
+        &["Enter"],
+        "Save to mtx_commands.sh",
+    ),
+    info(
+        KeyAction::StatisticsTags,
+        "statistics-tags",
+        KeyContext::CommandPreview,
+        &["t"],
+        "Regenerate statistics",
+    ),
+    info(
+        KeyAction::DeleteStatisticsTags,
+        "delete-statistics-tags",
+        KeyContext::CommandPreview,
+        &["d"],
+        "Delete statistics",
+    ),
+    info(
+        KeyAction::GlobalTags,
+        "global-tags",
+        KeyContext::CommandPreview,
+        &["g"],
+        "Set global from file",
+    ),
+    info(
+        KeyAction::ClearGlobalTags,
+        "clear-global-tags",
+        KeyContext::CommandPreview,
+        &["c"],
+        "Clear global",
+    ),
+    info(
+        KeyAction::ToggleSuggestion,
+        "toggle-suggestion",
+        KeyContext::Suggestions,
+        &["Space"],
+        "Toggle suggestion",
+    ),
+    info(
+        KeyAction::FollowLog,
+        "follow-log",
+        KeyContext::CommandLog,
+        &["End", "G"],
+        "Follow the log",
+    ),
+];
+
+/// Contexts that are active at the same time, their keys must differ.
+const ACTIVE_CONTEXTS: [&[KeyContext]; 7] = [
+    &[
+        KeyContext::GroupList,
+        KeyContext::Navigation,
+        KeyContext::Global,
+        KeyContext::GroupTab,
+    ],
+    &[
+        KeyContext::TrackTable,
+        KeyContext::Navigation,
+        KeyContext::Global,
+        KeyContext::GroupTab,
+    ],
+    &[
+        KeyContext::Navigation,
+        KeyContext::Global,
+        KeyContext::Problems,
+    ],
+    &[KeyContext::Navigation, KeyContext::CommandPreview],
+    &[
+        KeyContext::Navigation,
+        KeyContext::Popup,
+        KeyContext::Suggestions,
+    ],
+    &[
+        KeyContext::Navigation,
+        KeyContext::Popup,
+        KeyContext::CommandLog,
+    ],
+    // The help popup
+    &[
+        KeyContext::Navigation,
+        KeyContext::Popup,
+        KeyContext::Global,
+    ],
+];
+
+fn action_info(action: KeyAction) -> &'static ActionInfo {
+    ACTIONS
+        .iter()
+        .find(|info| info.action == action)
+        .expect("all actions are listed")
+}
+
+/// A key with the modifiers that have to be held. Shift is part of the character,
+/// e.g. `U`, so it is not compared.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) struct KeyBinding {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+const NAMED_KEYS: [(&str, KeyCode); 14] = [
+    ("Up", KeyCode::Up),
+    ("Down", KeyCode::Down),
+    ("Left", KeyCode::Left),
+    ("Right", KeyCode::Right),
+    ("Enter", KeyCode::Enter),
+    ("Esc", KeyCode::Esc),
+    ("Tab", KeyCode::Tab),
+    ("Backspace", KeyCode::Backspace),
+    ("Delete", KeyCode::Delete),
+    ("Home", KeyCode::Home),
+    ("End", KeyCode::End),
+    ("PageUp", KeyCode::PageUp),
+    ("PageDown", KeyCode::PageDown),
+    ("Space", KeyCode::Char(' ')),
+];
+
+impl KeyBinding {
+    /// Parses keys like `q`, `U`, `F2`, `Enter` or `ctrl-r`.
+    pub(crate) fn parse(text: &str) -> Result<Self> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = text;
+        loop {
+            if let Some(stripped) = rest.strip_prefix("ctrl-").filter(|s| !s.is_empty()) {
+                modifiers |= KeyModifiers::CONTROL;
+                rest = stripped;
+            } else if let Some(stripped) = rest.strip_prefix("alt-").filter(|s| !s.is_empty()) {
+                modifiers |= KeyModifiers::ALT;
+                rest = stripped;
+            } else {
+                break;
+            }
+        }
+        let mut chars = rest.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ => {
+                let function_key = rest
+                    .strip_prefix(['F', 'f'])
+                    .and_then(|no| no.parse::<u8>().ok())
+                    .filter(|no| (1..=12).contains(no));
+                match function_key {
+                    Some(no) => KeyCode::F(no),
+                    None => NAMED_KEYS
+                        .iter()
+                        .find(|(name, _)| name.eq_ignore_ascii_case(rest))
+                        .map(|(_, code)| *code)
+                        .ok_or_else(|| anyhow!("Unknown key {}", text))?,
+                }
+            }
+        };
+        Ok(KeyBinding { code, modifiers })
+    }
+
+    fn matches(&self, key_event: &KeyEvent) -> bool {
+        let modifiers = key_event.modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT);
+        self.code == key_event.code && self.modifiers == modifiers
+    }
+}
+
+impl std::fmt::Display for KeyBinding {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "ctrl-")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "alt-")?;
+        }
+        match self.code {
+            KeyCode::Char(c) if c != ' ' => write!(f, "{}", c),
+            KeyCode::F(no) => write!(f, "F{}", no),
+            code => {
+                let name = NAMED_KEYS
+                    .iter()
+                    .find(|(_, named)| *named == code)
+                    .map(|(name, _)| *name)
+                    .unwrap_or("?");
+                write!(f, "{}", name)
+            }
+        }
+    }
+}
+
+/// A single key or a list of keys in the keymap file.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum KeyList {
+    One(String),
+    Many(Vec<String>),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct KeymapFile {
+    #[serde(default)]
+    keys: BTreeMap<String, KeyList>,
+}
+
+/// Keys of the TUI actions. A keymap file replaces the keys of the actions it lists:
+///
+/// ```toml
+/// [keys]
+/// quit = ["q", "ctrl-c"]
+/// down = ["Down", "j"]
+/// group-commands = "F5"
+/// ```
+#[derive(Clone, Debug)]
+pub(crate) struct Keymap {
+    bindings: HashMap<KeyAction, Vec<KeyBinding>>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let bindings = ACTIONS
+            .iter()
+            .map(|info| {
+                let keys = info
+                    .keys
+                    .iter()
+                    .map(|key| KeyBinding::parse(key).expect("default keys are valid"))
+                    .collect();
+                (info.action, keys)
+            })
+            .collect();
+        Keymap { bindings }
+    }
+}
+
+impl Keymap {
+    pub(crate) fn from_str(content: &str) -> Result<Self> {
+        let file: KeymapFile = toml::from_str(content)?;
+        let mut keymap = Keymap::default();
+        for (id, keys) in file.keys {
+            let info = ACTIONS.iter().find(|info| info.id == id).ok_or_else(|| {
+                anyhow!(
+                    "Unknown action {}, known actions: {}",
+                    id,
+                    ACTIONS
+                        .iter()
+                        .map(|info| info.id)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            })?;
+            let keys = match keys {
+                KeyList::One(key) => vec![key],
+                KeyList::Many(keys) => keys,
+            };
+            if keys.is_empty() {
+                bail!("No keys for action {}", id);
+            }
+            let keys = keys
+                .iter()
+                .map(|key| KeyBinding::parse(key))
+                .collect::<Result<_>>()
+                .with_context(|| format!("Invalid key for action {}", id))?;
+            keymap.bindings.insert(info.action, keys);
+        }
+        Ok(keymap)
+    }
+
+    pub(crate) fn from_path(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Could not read keymap {}", path.display()))?;
+        Self::from_str(&content).with_context(|| format!("Invalid keymap {}", path.display()))
+    }
+
+    /// The first action of the contexts that is bound to the key.
+    pub(crate) fn action(&self, key_event: KeyEvent, contexts: &[KeyContext]) -> Option<KeyAction> {
+        ACTIONS
+            .iter()
+            .filter(|info| contexts.contains(&info.context))
+            .find(|info| {
+                self.bindings
+                    .get(&info.action)
+                    .is_some_and(|keys| keys.iter().any(|key| key.matches(&key_event)))
+            })
+            .map(|info| info.action)
+    }
+
+    /// Keys of the action separated by slashes, e.g. `Up/k`.
+    pub(crate) fn keys(&self, action: KeyAction) -> String {
+        self.bindings
+            .get(&action)
+            .map(|keys| keys.iter().map(KeyBinding::to_string).collect::<Vec<_>>())
+            .unwrap_or_default()
+            .join("/")
+    }
+
+    /// Short hint like `F2: Apply group | ?: Help`.
+    pub(crate) fn hint(&self, actions: &[KeyAction]) -> String {
+        actions
+            .iter()
+            .map(|action| {
+                format!(
+                    "{}: {}",
+                    self.keys(*action),
+                    action_info(*action).description
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(" | ")
+    }
+
+    /// Keys that are bound to several actions which are active at the same time.
+    pub(crate) fn conflicts(&self) -> Vec<String> {
+        let mut conflicts = Vec::new();
+        for contexts in ACTIVE_CONTEXTS {
+            let infos: Vec<&ActionInfo> = ACTIONS
+                .iter()
+                .filter(|info| contexts.contains(&info.context))
+                .collect();
+            for (idx, first) in infos.iter().enumerate() {
+                for second in &infos[idx + 1..] {
+                    for key in &self.bindings[&first.action] {
+                        let conflict =
+                            format!("Key {} is bound to {} and {}", key, first.id, second.id);
+                        if self.bindings[&second.action].contains(key)
+                            && !conflicts.contains(&conflict)
+                        {
+                            conflicts.push(conflict);
+                        }
+                    }
+                }
+            }
+        }
+        conflicts
+    }
+
+    /// Lines that list the keys, descriptions and names of all actions of the contexts.
+    pub(crate) fn help(&self, contexts: &[KeyContext]) -> Vec<String> {
+        let mut lines = Vec::new();
+        for context in contexts {
+            if !lines.is_empty() {
+                lines.push(String::new());
+            }
+            lines.push(context.title().to_owned());
+            lines.extend(
+                ACTIONS
+                    .iter()
+                    .filter(|info| info.context == *context)
+                    .map(|info| {
+                        format!(
+                            "  {:<14} {:<26} {}",
+                            self.keys(info.action),
+                            info.description,
+                            info.id
+                        )
+                    }),
+            );
+        }
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent::new(code, modifiers)
+    }
+
+    #[test]
+    fn test_parse() {
+        let redo = KeyBinding::parse("ctrl-r").unwrap();
+        assert!(redo.matches(&key(KeyCode::Char('r'), KeyModifiers::CONTROL)));
+        assert!(!redo.matches(&key(KeyCode::Char('r'), KeyModifiers::NONE)));
+        assert!(
+            KeyBinding::parse("U")
+                .unwrap()
+                .matches(&key(KeyCode::Char('U'), KeyModifiers::SHIFT))
+        );
+        assert_eq!(KeyBinding::parse("F2").unwrap().to_string(), "F2");
+        assert_eq!(KeyBinding::parse("space").unwrap().to_string(), "Space");
+        assert_eq!(KeyBinding::parse("F").unwrap().code, KeyCode::Char('F'));
+        assert_eq!(KeyBinding::parse("ctrl--").unwrap().to_string(), "ctrl--");
+        assert!(KeyBinding::parse("Hyper").is_err());
+    }
+
+    #[test]
+    fn test_keymap_file() {
+        let keymap = Keymap::from_str(
+            r#"
+            [keys]
+            quit = ["q", "ctrl-c"]
+            group-commands = "F5"
+            "#,
+        )
+        .unwrap();
+        let quit = key(KeyCode::Char('c'), KeyModifiers::CONTROL);
+        assert_eq!(
+            keymap.action(quit, &[KeyContext::Global]),
+            Some(KeyAction::Quit)
+        );
+        let f5 = key(KeyCode::F(5), KeyModifiers::NONE);
+        assert_eq!(
+            keymap.action(f5, &[KeyContext::GroupTab]),
+            Some(KeyAction::GroupCommands)
+        );
+        // Keys of other contexts do not match
+        assert_eq!(keymap.action(f5, &[KeyContext::Problems]), None);
+        assert_eq!(keymap.keys(KeyAction::Down), "Down/j");
+        assert!(Keymap::from_str("[keys]\nunknown = \"x\"").is_err());
+        assert!(Keymap::from_str("[keys]\nquit = \"Hyper\"").is_err());
+    }
+
+    #[test]
+    fn test_conflicts() {
+        assert!(Keymap::default().conflicts().is_empty());
+        let keymap = Keymap::from_str("[keys]\nrename = \"n\"\nrun-commands = \"t\"").unwrap();
+        assert_eq!(
+            keymap.conflicts(),
+            vec![
+                "Key n is bound to rename and search-next",
+                "Key t is bound to run-commands and statistics-tags",
+            ]
+        );
+        // Keys of contexts that are never active together do not conflict
+        let keymap = Keymap::from_str("[keys]\nvisual = \"/\"").unwrap();
+        assert!(keymap.conflicts().is_empty());
+    }
+}
@@ -1,14 +1,31 @@
 mod group_files_list_widget;
 mod group_list_widget;
+mod info_widget;
+mod keymap;
 mod popup;
+mod problems_tab;
 mod selectable_state;
+mod theme;
 mod track_table_widget;
+use crate::analyze::{Suggestion, suggest_from_keys};
 use crate::command::Command;
 use crate::file::File;
-use crate::group::{groupby, key_audlang_audname, key_sublang_subname};
-use crate::ui::popup::{CommandRunnerPopup, MessagePopup, PopupRender};
+use crate::group::{
+    FileSort, GroupKey, GroupSort, groupby, key_audlang_audname, key_sublang_subname, sort_groups,
+};
+use crate::lint::{LintConfig, lint_files};
+use crate::naming::{NameRule, NameTemplate, RenameScope, rename_commands};
+use crate::policy::DefaultPolicy;
+use crate::stats::LibraryStats;
+use crate::track_operations::{EditPlan, FileOperation};
+use crate::ui::info_widget::InfoWidget;
+use crate::ui::keymap::{KeyAction, KeyContext};
+use crate::ui::popup::{CommandRunnerPopup, HelpPopup, MessagePopup, PopupRender, SuggestionPopup};
+use crate::ui::problems_tab::ProblemsTabData;
+pub(crate) use keymap::Keymap;
+pub(crate) use theme::{THEMES, Theme};
 
-use self::popup::EditPopup;
+use self::popup::{EditPopup, EditTarget};
 
 use super::file::TrackType;
 use super::group::Group;
@@ -16,9 +33,12 @@ use super::ui::group_files_list_widget::GroupFilesListWidget;
 use super::ui::group_list_widget::GroupListWidget;
 use super::ui::popup::{CommandPopup, PopupRenderer};
 use super::ui::selectable_state::SelectableState;
-use super::ui::track_table_widget::TrackTableWidget;
+use super::ui::track_table_widget::{EditHistory, TrackTableWidget};
 use crossterm::{
-    event::{self, Event as CEvent, KeyCode},
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, Event as CEvent, MouseButton, MouseEvent,
+        MouseEventKind,
+    },
     execute,
     terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
 };
@@ -26,18 +46,18 @@ use ratatui::Frame;
 use ratatui::{
     Terminal,
     backend::CrosstermBackend,
-    layout::{Alignment, Constraint, Direction, Layout, Rect},
+    layout::{Alignment, Constraint, Direction, Layout, Margin, Position, Rect},
     style::{Color, Modifier, Style},
     text::{Line, Span},
     widgets::{Block, BorderType, Borders, Paragraph, Tabs},
 };
+use std::collections::{BTreeMap, HashSet};
 use std::io;
+use std::path::{Path, PathBuf};
 use std::sync::mpsc;
 use std::thread;
 use std::time::{Duration, Instant};
 
-const SEL_COLOR: Color = Color::Cyan;
-
 enum Event<I> {
     Input(I),
     Tick,
@@ -48,6 +68,7 @@ pub(crate) enum MenuItem {
     Home,
     Subs,
     Audio,
+    Problems,
 }
 
 impl From<MenuItem> for usize {
@@ -56,6 +77,7 @@ impl From<MenuItem> for usize {
             MenuItem::Home => 0,
             MenuItem::Subs => 1,
             MenuItem::Audio => 2,
+            MenuItem::Problems => 3,
         }
     }
 }
@@ -76,13 +98,21 @@ pub(crate) enum CommandType {
 pub(crate) enum Action {
     NavigateForward(ActiveWidget),
     NavigateBackward(ActiveWidget),
+    /// The widget was clicked and takes the focus.
+    Focus(ActiveWidget),
     EditString(String),
-    EditStringResult(Option<String>),
+    EditGlobalTags,
+    EditStringResult(EditTarget, Option<String>),
     ShowMessage(String),
     LoadGroup,
     SwitchTab(MenuItem),
     RunCommands((CommandType, Vec<Command>)), // this is incredibly stupid
     CommandsDone((CommandType, Vec<Command>)),
+    ToggleFileOperation(FileOperation),
+    ApplySuggestions(Vec<Suggestion>),
+    ApplyPending,
+    CycleGroupSort,
+    CycleFileSort,
     ClosePopup,
     ReloadFiles(Vec<File>),
     Quit,
@@ -112,29 +142,34 @@ impl FocusState {
         }
     }
 
-    fn text_color(&self) -> Color {
+    fn text_color(&self, theme: &Theme) -> Color {
         match self {
-            FocusState::Background => Color::DarkGray,
-            FocusState::Foreground => Color::White,
-            FocusState::Highlight => Color::White,
+            FocusState::Background => theme.dimmed,
+            FocusState::Foreground => theme.text,
+            FocusState::Highlight => theme.text,
         }
     }
 
-    fn sel_color(&self) -> Color {
+    fn sel_color(&self, theme: &Theme) -> Color {
         match self {
-            FocusState::Background => Color::DarkGray,
-            FocusState::Foreground => Color::Cyan,
-            FocusState::Highlight => Color::Cyan,
+            FocusState::Background => theme.dimmed,
+            FocusState::Foreground => theme.accent,
+            FocusState::Highlight => theme.accent,
         }
     }
 
-    fn border_color(&self) -> Color {
+    fn border_color(&self, theme: &Theme) -> Color {
         match self {
-            FocusState::Background => Color::DarkGray,
-            FocusState::Foreground => Color::White,
-            FocusState::Highlight => Color::Cyan,
+            FocusState::Background => theme.dimmed,
+            FocusState::Foreground => theme.border,
+            FocusState::Highlight => theme.accent,
         }
     }
+
+    /// Style of the selected item of a list or table.
+    fn selection(&self, theme: &Theme) -> Style {
+        theme.selection(self.sel_color(theme))
+    }
 }
 
 fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
@@ -221,8 +256,79 @@ fn centered_rect_fit_text(text: &str, margin_x: u16, margin_y: u16, r: Rect) ->
         .split(popup_layout[1])[1]
 }
 
+/// Existing configuration file in `$XDG_CONFIG_HOME/mtxstuff` or `~/.config/mtxstuff`.
+pub(crate) fn config_file(file_name: &str) -> Option<PathBuf> {
+    let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config_dir.join("mtxstuff").join(file_name)).filter(|path| path.is_file())
+}
+
+/// Index of the item of a bordered list or table at the position of a mouse event, the first
+/// `header` lines inside the border are skipped.
+fn item_at(area: Rect, mouse_event: MouseEvent, header: u16, offset: usize) -> Option<usize> {
+    let inner = area.inner(Margin::new(1, 1));
+    if !inner.contains(Position::new(mouse_event.column, mouse_event.row))
+        || mouse_event.row < inner.y + header
+    {
+        return None;
+    }
+    Some((mouse_event.row - inner.y - header) as usize + offset)
+}
+
+/// Index of the clicked title of the menu tabs, the titles are padded by one space on each
+/// side and separated by a one column divider.
+fn clicked_tab(area: Rect, titles: &[&str], mouse_event: MouseEvent) -> Option<usize> {
+    if mouse_event.row != area.y + 1 {
+        return None;
+    }
+    let mut x = area.x + 1;
+    for (idx, title) in titles.iter().enumerate() {
+        let width = title.width() as u16 + 2;
+        if (x..x + width).contains(&mouse_event.column) {
+            return Some(idx);
+        }
+        x += width + 1;
+    }
+    None
+}
+
+/// Commands that identify the files edited by the given mkvpropedit commands again.
+fn reload_commands(altered: &[Command]) -> Vec<Command> {
+    altered
+        .iter()
+        // Commands that were cancelled did not change their file
+        .filter(|altered| altered.output.is_some())
+        .filter_map(|altered| altered.file_name())
+        .map(|file_name| {
+            let mut command = Command::new("mkvmerge");
+            command
+                .arguments
+                .push("--identification-format".to_string());
+            command.arguments.push("json".to_string());
+            command.arguments.push("--identify".to_string());
+            command.arguments.push(file_name.to_string());
+            command
+        })
+        .collect()
+}
+
+fn reloaded_files(commands: &[Command]) -> Vec<File> {
+    commands
+        .iter()
+        .filter_map(|c| c.output.as_ref())
+        .filter(|c| c.status.success())
+        .map(|output| File::from_json_str(&output.stdout).unwrap())
+        .collect()
+}
+
 pub(crate) trait KeyPressConsumer {
-    fn process_key(&mut self, key_code: crossterm::event::KeyCode) -> Action;
+    fn process_key(&mut self, key_event: crossterm::event::KeyEvent, keymap: &Keymap) -> Action;
+
+    fn process_mouse(&mut self, _mouse_event: MouseEvent) -> Action {
+        Action::Pass
+    }
 
     fn check_auto_close(&mut self) -> Action {
         Action::Pass
@@ -238,51 +344,129 @@ struct GroupTabData<'a> {
     active_widget: ActiveWidget,
     popup_data: PopupRenderer,
     track_type: TrackType,
+    file_ops: Vec<FileOperation>,
+    /// Last entered global tags file.
+    global_tags: String,
+    policy: DefaultPolicy,
+    /// Last template used for renaming, offered again when renaming the next group.
+    name_template: String,
+    edits: GroupEdits,
+    /// Index of the group shown in the track table.
+    loaded_group: Option<usize>,
+    keymap: &'a Keymap,
+    theme: &'a Theme,
+}
+
+/// Edits of the groups of a tab, by the original key of the group, and the state of the group
+/// list. They are kept when the groups are rebuilt.
+#[derive(Default)]
+struct GroupEdits {
+    /// Edited keys that are not applied yet.
+    pending: BTreeMap<Vec<GroupKey>, Vec<GroupKey>>,
+    history: BTreeMap<Vec<GroupKey>, EditHistory>,
+    /// Key of the selected group.
+    selected: Option<Vec<GroupKey>>,
+    query: String,
 }
 
-impl<'a> KeyPressConsumer for GroupTabData<'a> {
+impl<'a> GroupTabData<'a> {
     fn check_auto_close(&mut self) -> Action {
         self.popup_data.check_auto_close()
     }
 
-    fn process_key(&mut self, key_code: crossterm::event::KeyCode) -> Action {
+    fn process_key(&mut self, key_event: crossterm::event::KeyEvent) -> Action {
+        let keymap = self.keymap;
         let res_action = if self.popup_data.active() {
-            self.popup_data.process_key(key_code)
+            self.popup_data.process_key(key_event, keymap)
         } else {
             match self.active_widget {
-                ActiveWidget::Groups => self.group_list.process_key(key_code),
-                ActiveWidget::Details => self.track_table.process_key(key_code),
-                ActiveWidget::Files => self.group_files_list.process_key(key_code),
+                ActiveWidget::Groups => self.group_list.process_key(key_event, keymap),
+                ActiveWidget::Details => self.track_table.process_key(key_event, keymap),
+                ActiveWidget::Files => self.group_files_list.process_key(key_event, keymap),
             }
         };
         // TODO: Match key_codes for GrouTabData itself (Tab switching, Quitting) if not in edit mode
         // For now, only do it if the active widget returned Action::Pass
-        if matches!(res_action, Action::Pass) && !self.popup_data.active() {
-            match key_code {
-                KeyCode::Char('i') => return Action::SwitchTab(MenuItem::Home),
-                KeyCode::Char('s') => return Action::SwitchTab(MenuItem::Subs),
-                KeyCode::Char('a') => return Action::SwitchTab(MenuItem::Audio),
-                KeyCode::Char('q') => return Action::Quit,
-                KeyCode::F(2) => {
+        if matches!(res_action, Action::Pass)
+            && !self.popup_data.active()
+            && !self.group_list.searching()
+        {
+            match keymap.action(key_event, &[KeyContext::Global, KeyContext::GroupTab]) {
+                Some(KeyAction::TabInfo) => return Action::SwitchTab(MenuItem::Home),
+                Some(KeyAction::TabSubs) => return Action::SwitchTab(MenuItem::Subs),
+                Some(KeyAction::TabAudio) => return Action::SwitchTab(MenuItem::Audio),
+                Some(KeyAction::TabProblems) => return Action::SwitchTab(MenuItem::Problems),
+                Some(KeyAction::Quit) => return Action::Quit,
+                Some(KeyAction::Help) => {
+                    self.show_help();
+                    return Action::Pass;
+                }
+                Some(KeyAction::GroupCommands) => {
                     self.generate_commands();
                     return Action::Pass;
                 }
+                Some(KeyAction::ApplyPending) => return Action::ApplyPending,
+                Some(KeyAction::SortGroups) => return Action::CycleGroupSort,
+                Some(KeyAction::SortFiles) => return Action::CycleFileSort,
+                Some(KeyAction::Undo) => {
+                    self.track_table.undo();
+                    return Action::Pass;
+                }
+                Some(KeyAction::Redo) => {
+                    self.track_table.redo();
+                    return Action::Pass;
+                }
+                Some(KeyAction::RevertAll) => {
+                    self.track_table.revert_all();
+                    return Action::Pass;
+                }
+                Some(KeyAction::ChooseDefault) => {
+                    let action = self.choose_default();
+                    return self.process_action(action);
+                }
+                Some(KeyAction::Suggestions) => {
+                    let action = self.review_suggestions();
+                    return self.process_action(action);
+                }
+                Some(KeyAction::Rename) => {
+                    let new_popup = EditPopup {
+                        input: self.name_template.clone(),
+                        target: EditTarget::NameTemplate,
+                    };
+                    self.popup_data.popup_stack.push(Box::new(new_popup));
+                    return Action::Pass;
+                }
                 _ => {}
             }
         }
         self.process_action(res_action)
     }
-}
 
-impl<'a> GroupTabData<'a> {
+    fn process_mouse(&mut self, mouse_event: MouseEvent) -> Action {
+        if self.popup_data.active() {
+            let action = self.popup_data.process_mouse(mouse_event);
+            return self.process_action(action);
+        }
+        // Only the widget under the mouse reacts
+        let widgets: [&mut dyn KeyPressConsumer; 3] = [
+            &mut self.group_list,
+            &mut self.track_table,
+            &mut self.group_files_list,
+        ];
+        let action = widgets
+            .into_iter()
+            .map(|widget| widget.process_mouse(mouse_event))
+            .find(|action| !matches!(action, Action::Pass))
+            .unwrap_or(Action::Pass);
+        self.process_action(action)
+    }
+
     fn process_action(&mut self, action: Action) -> Action {
         match action {
             Action::NavigateForward(src_widget) => match src_widget {
-                ActiveWidget::Details => {
-                    if self.group_files_list.try_enter() {
-                        self.track_table.leave();
-                        self.active_widget = ActiveWidget::Files;
-                    }
+                ActiveWidget::Details if self.group_files_list.try_enter() => {
+                    self.track_table.leave();
+                    self.active_widget = ActiveWidget::Files;
                 }
                 ActiveWidget::Groups => {
                     if self.track_table.try_enter() {
@@ -293,6 +477,18 @@ impl<'a> GroupTabData<'a> {
                 }
                 _ => {}
             },
+            Action::Focus(widget) => {
+                if widget != ActiveWidget::Details {
+                    self.track_table.leave();
+                }
+                if widget != ActiveWidget::Files {
+                    self.group_files_list.leave();
+                }
+                self.active_widget = widget;
+                if widget == ActiveWidget::Groups {
+                    return self.process_action(Action::LoadGroup);
+                }
+            }
             Action::ClosePopup => {
                 self.popup_data.popup_stack.pop();
             }
@@ -319,89 +515,148 @@ impl<'a> GroupTabData<'a> {
                 _ => {}
             },
             Action::EditString(string) => {
-                let new_popup = EditPopup { input: string };
+                let new_popup = EditPopup {
+                    input: string,
+                    target: EditTarget::TrackKey,
+                };
+                self.popup_data.popup_stack.push(Box::new(new_popup));
+            }
+            Action::EditGlobalTags => {
+                let new_popup = EditPopup {
+                    input: self.global_tags.clone(),
+                    target: EditTarget::GlobalTags,
+                };
                 self.popup_data.popup_stack.push(Box::new(new_popup));
             }
             Action::ShowMessage(string) => {
                 let new_popup = MessagePopup { message: string };
                 self.popup_data.popup_stack.push(Box::new(new_popup));
             }
-            Action::EditStringResult(res) => {
+            Action::EditStringResult(EditTarget::GlobalTags, res) => {
+                self.popup_data.popup_stack.pop();
+                if let Some(path) = res {
+                    self.global_tags = path.clone();
+                    if !Path::new(&path).is_file() {
+                        return self.process_action(Action::ShowMessage(format!(
+                            "Tags file {} does not exist",
+                            path
+                        )));
+                    }
+                    return self.process_action(Action::ToggleFileOperation(
+                        FileOperation::SetGlobalTags(path),
+                    ));
+                }
+            }
+            Action::EditStringResult(EditTarget::NameTemplate, res) => {
+                self.popup_data.popup_stack.pop();
+                if let Some(template) = res {
+                    self.name_template = template;
+                    return self.rename_tracks();
+                }
+            }
+            Action::EditStringResult(EditTarget::TrackKey, res) => {
                 if let Some(string) = res {
-                    let row = self
-                        .track_table
-                        .selected()
-                        .expect("Currently edited item must be selected");
                     let col = self
                         .track_table
                         .selected_col
                         .expect("Currently edited item must be selected");
-
-                    let group_key = self
-                        .track_table
-                        .get_keys_copy_mut()
-                        .get_mut(row)
-                        .expect("Currently edit item must exist");
-                    match col {
-                        0 => {
-                            group_key.language = Some(string);
-                        }
-                        1 => {
-                            group_key.name = Some(string);
-                        }
-                        _ => {}
-                    };
+                    // All rows of a visual selection get the same value
+                    let rows = self.track_table.marked_rows();
+                    let keys = self.track_table.get_keys_copy_mut();
+                    for row in rows {
+                        let group_key = keys.get_mut(row).expect("Currently edit item must exist");
+                        match col {
+                            0 => {
+                                group_key.language = Some(string.clone());
+                            }
+                            1 => {
+                                group_key.name = Some(string.clone());
+                            }
+                            _ => {}
+                        };
+                    }
                 }
                 self.popup_data.popup_stack.pop();
             }
-            Action::LoadGroup => self.load_selected_group(),
-            Action::RunCommands((command_type, commands)) => {
-                let new_popup =
-                    CommandRunnerPopup::new(commands, command_type, "Applying changes".to_string());
-                self.popup_data.popup_stack.push(Box::new(new_popup));
+            Action::LoadGroup => {
+                if self.group_list.selected_group() != self.loaded_group {
+                    self.load_selected_group();
+                }
             }
-            Action::CommandsDone((CommandType::AlterFiles, _)) => {
+            Action::ApplySuggestions(suggestions) => {
+                let keys = self.track_table.get_keys_copy_mut();
+                for suggestion in suggestions {
+                    if let Some(key) = keys.get_mut(suggestion.track_no) {
+                        suggestion.apply(key);
+                    }
+                }
                 self.popup_data.popup_stack.pop();
-                let mut commands: Vec<Command> = Vec::new();
-                let files = self.selected_group().unwrap().files.as_slice();
-                for file in files {
-                    let mut command = Command::new("mkvmerge");
-                    command
-                        .arguments
-                        .push("--identification-format".to_string());
-                    command.arguments.push("json".to_string());
-                    command.arguments.push("--identify".to_string());
-                    command.arguments.push(file.file_name.clone());
-                    commands.push(command);
+            }
+            Action::ToggleFileOperation(file_op) => {
+                if let Some(pos) = self.file_ops.iter().position(|op| *op == file_op) {
+                    self.file_ops.remove(pos);
+                } else {
+                    self.file_ops.retain(|op| !op.conflicts_with(&file_op));
+                    self.file_ops.push(file_op);
                 }
+                // Regenerate the command preview
+                self.popup_data.popup_stack.pop();
+                self.generate_commands();
+            }
+            Action::RunCommands((command_type, commands)) => {
                 let new_popup = CommandRunnerPopup::new(
                     commands,
+                    command_type,
+                    "Applying changes".to_string(),
+                    self.keymap,
+                );
+                self.popup_data.popup_stack.push(Box::new(new_popup));
+            }
+            Action::CommandsDone((CommandType::AlterFiles, commands)) => {
+                self.popup_data.popup_stack.pop();
+                let new_popup = CommandRunnerPopup::new(
+                    reload_commands(&commands),
                     CommandType::ReloadFiles,
                     "Reloading files".to_string(),
+                    self.keymap,
                 );
                 self.popup_data.popup_stack.push(Box::new(new_popup));
             }
             Action::CommandsDone((CommandType::ReloadFiles, commands)) => {
                 self.popup_data.popup_stack.pop();
                 self.popup_data.popup_stack.pop();
-                let changed_files: Vec<File> = commands
-                    .iter()
-                    .map(|c| c.output.as_ref().unwrap())
-                    .filter(|c| c.status.success())
-                    .map(|output| File::from_json_str(&output.stdout).unwrap())
-                    .collect();
-                return Action::ReloadFiles(changed_files);
+                return Action::ReloadFiles(reloaded_files(&commands));
             }
             switch_tab @ Action::SwitchTab(_) => return switch_tab,
+            Action::ApplyPending => return Action::ApplyPending,
+            Action::CycleGroupSort => return Action::CycleGroupSort,
+            Action::CycleFileSort => return Action::CycleFileSort,
             Action::Quit => return Action::Quit,
             Action::Pass => {}
         }
         Action::Pass
     }
 
-    fn new(groups: &'a [Group<'a>], track_type: TrackType) -> Self {
+    fn new(
+        groups: &'a [Group<'a>],
+        track_type: TrackType,
+        policy: DefaultPolicy,
+        mut edits: GroupEdits,
+        keymap: &'a Keymap,
+        theme: &'a Theme,
+    ) -> Self {
+        // Groups that changed when reloading the files are gone
+        let exists = |key: &Vec<GroupKey>| groups.iter().any(|group| group.key == *key);
+        edits.pending.retain(|key, _| exists(key));
+        edits.history.retain(|key, _| exists(key));
+        let mut group_list = GroupListWidget::new(groups);
+        let selected = edits
+            .selected
+            .take()
+            .and_then(|key| groups.iter().position(|group| group.key == key));
+        group_list.restore(std::mem::take(&mut edits.query), selected);
         GroupTabData {
-            group_list: GroupListWidget::new(groups.len()),
+            group_list,
             track_table: TrackTableWidget::default(),
             group_files_list: GroupFilesListWidget::default(),
             groups,
@@ -410,28 +665,218 @@ impl<'a> GroupTabData<'a> {
                 popup_stack: Vec::new(),
             },
             track_type,
+            file_ops: Vec::new(),
+            global_tags: String::new(),
+            policy,
+            name_template: String::new(),
+            edits,
+            loaded_group: None,
+            keymap,
+            theme,
         }
     }
 
+    /// Key context of the focused widget.
+    fn widget_context(&self) -> Option<KeyContext> {
+        match self.active_widget {
+            ActiveWidget::Groups => Some(KeyContext::GroupList),
+            ActiveWidget::Details => Some(KeyContext::TrackTable),
+            ActiveWidget::Files => None,
+        }
+    }
+
+    fn show_help(&mut self) {
+        let contexts: Vec<KeyContext> = self
+            .widget_context()
+            .into_iter()
+            .chain([
+                KeyContext::GroupTab,
+                KeyContext::Navigation,
+                KeyContext::Global,
+                KeyContext::Popup,
+                KeyContext::CommandPreview,
+                KeyContext::Suggestions,
+                KeyContext::CommandLog,
+            ])
+            .collect();
+        let new_popup = HelpPopup::new(self.keymap, &contexts);
+        self.popup_data.popup_stack.push(Box::new(new_popup));
+    }
+
+    /// Keys shown in the status bar for the focused widget.
+    fn hint(&self) -> String {
+        let actions: &[KeyAction] = match self.active_widget {
+            ActiveWidget::Groups => &[
+                KeyAction::Search,
+                KeyAction::SortGroups,
+                KeyAction::GroupCommands,
+                KeyAction::ApplyPending,
+                KeyAction::Help,
+            ],
+            ActiveWidget::Details => &[
+                KeyAction::Edit,
+                KeyAction::Visual,
+                KeyAction::Undo,
+                KeyAction::GroupCommands,
+                KeyAction::ApplyPending,
+                KeyAction::Help,
+            ],
+            ActiveWidget::Files => &[
+                KeyAction::Back,
+                KeyAction::GroupCommands,
+                KeyAction::ApplyPending,
+                KeyAction::Help,
+            ],
+        };
+        self.keymap.hint(actions)
+    }
+
+    fn review_suggestions(&mut self) -> Action {
+        let suggestions = suggest_from_keys(self.track_table.get_keys_copy(), self.track_type);
+        if suggestions.is_empty() {
+            return Action::ShowMessage("No suggestions for this group".to_string());
+        }
+        let new_popup = SuggestionPopup::new(suggestions, self.keymap);
+        self.popup_data.popup_stack.push(Box::new(new_popup));
+        Action::Pass
+    }
+
+    /// Marks the track chosen by the default policy as exclusive default track.
+    /// The first file of the group is representative for the whole group.
+    fn choose_default(&mut self) -> Action {
+        let Some(file) = self.selected_group().and_then(|group| group.files.first()) else {
+            return Action::Pass;
+        };
+        match self.policy.choose(file.tracks(self.track_type)) {
+            Some(chosen) => {
+                self.track_table.set_default_exclusive(chosen);
+                Action::Pass
+            }
+            None => Action::ShowMessage("No track to choose from".to_string()),
+        }
+    }
+
+    /// Renames all tracks of the selected group with the current name template and
+    /// shows the commands together with the old and new names per file.
+    fn rename_tracks(&mut self) -> Action {
+        let template = match NameTemplate::parse(&self.name_template) {
+            Ok(template) => template,
+            Err(err) => return self.process_action(Action::ShowMessage(err.to_string())),
+        };
+        let Some(sel_group) = self.selected_group() else {
+            return Action::Pass;
+        };
+        let (commands, preview) = rename_commands(
+            &NameRule::Template(template),
+            sel_group.files.iter().copied(),
+            &RenameScope::new(self.track_type),
+            &EditPlan::new(),
+        );
+        if commands.is_empty() {
+            return self.process_action(Action::ShowMessage(
+                "All names already match the template".to_string(),
+            ));
+        }
+        let command_popup =
+            CommandPopup::new(commands, Vec::new(), self.keymap).with_preview(preview);
+        self.popup_data.popup_stack.push(Box::new(command_popup));
+        Action::Pass
+    }
+
     fn generate_commands(&mut self) {
         let sel_group = self.selected_group().unwrap();
-        let commands = sel_group.apply_changes(self.track_table.get_keys_copy(), self.track_type);
-        let command_popup = CommandPopup::new(commands);
+        let commands = sel_group.apply_changes(
+            self.track_table.get_keys_copy(),
+            self.track_type,
+            &self.file_ops,
+        );
+        let command_popup = CommandPopup::new(commands, self.file_ops.clone(), self.keymap);
         self.popup_data.popup_stack.push(Box::new(command_popup));
     }
 
     fn load_selected_group(&mut self) {
-        self.track_table = TrackTableWidget::from_group(self.selected_group());
+        self.store_pending();
+        self.track_table = TrackTableWidget::from_group(self.selected_group(), self.track_type);
         self.group_files_list = GroupFilesListWidget::from_group(self.selected_group());
+        self.loaded_group = self.group_list.selected_group();
+        let Some(group) = self.loaded_group.and_then(|idx| self.groups.get(idx)) else {
+            return;
+        };
+        if let Some(keys) = self.edits.pending.get(&group.key) {
+            self.track_table.set_keys_copy(keys.clone());
+        }
+        if let Some(history) = self.edits.history.remove(&group.key) {
+            self.track_table.set_history(history);
+        }
+    }
+
+    /// Remembers the edits and the edit history of the group shown in the track table.
+    fn store_pending(&mut self) {
+        let Some(group) = self.loaded_group.and_then(|idx| self.groups.get(idx)) else {
+            return;
+        };
+        let keys = self.track_table.get_keys_copy();
+        if group.key.as_slice() == keys {
+            self.edits.pending.remove(&group.key);
+        } else {
+            self.edits.pending.insert(group.key.clone(), keys.to_vec());
+        }
+        let history = self.track_table.take_history();
+        if !history.is_empty() {
+            self.edits.history.insert(group.key.clone(), history);
+        }
+    }
+
+    fn take_edits(&mut self) -> GroupEdits {
+        self.store_pending();
+        self.edits.selected = self.selected_group().map(|group| group.key.clone());
+        self.edits.query = self.group_list.query().to_owned();
+        std::mem::take(&mut self.edits)
+    }
+
+    /// All groups with edits that are not applied yet, with their index and the edited keys.
+    fn pending_changes(&mut self) -> Vec<(usize, &'a Group<'a>, Vec<GroupKey>)> {
+        self.store_pending();
+        self.groups
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, group)| {
+                self.edits
+                    .pending
+                    .get(&group.key)
+                    .map(|keys| (idx, group, keys.clone()))
+            })
+            .collect()
+    }
+
+    fn modified_groups(&self) -> Vec<bool> {
+        self.groups
+            .iter()
+            .enumerate()
+            .map(|(idx, group)| {
+                if Some(idx) == self.loaded_group {
+                    group.key.as_slice() != self.track_table.get_keys_copy()
+                } else {
+                    self.edits.pending.contains_key(&group.key)
+                }
+            })
+            .collect()
+    }
+
+    fn show_commands(&mut self, commands: Vec<Command>, preview: Vec<String>) {
+        let command_popup =
+            CommandPopup::new(commands, Vec::new(), self.keymap).with_preview(preview);
+        self.popup_data.popup_stack.push(Box::new(command_popup));
     }
 
     fn selected_group(&'_ self) -> Option<&'_ Group<'_>> {
         self.group_list
-            .selected()
+            .selected_group()
             .and_then(|selected| self.groups.get(selected))
     }
 
     fn render(&mut self, frame: &mut Frame, area: Rect) {
+        self.group_list.set_modified(self.modified_groups());
         let horiz_split = Layout::default()
             .direction(Direction::Horizontal)
             .constraints([Constraint::Percentage(20), Constraint::Percentage(80)].as_ref())
@@ -449,6 +894,7 @@ impl<'a> GroupTabData<'a> {
                 GroupFilesListWidget::widget_type(),
                 self.popup_data.active(),
             ),
+            self.theme,
         );
         self.track_table.render(
             frame,
@@ -458,6 +904,7 @@ impl<'a> GroupTabData<'a> {
                 TrackTableWidget::widget_type(),
                 self.popup_data.active(),
             ),
+            self.theme,
         );
         self.group_list.render(
             frame,
@@ -467,14 +914,69 @@ impl<'a> GroupTabData<'a> {
                 GroupListWidget::widget_type(),
                 self.popup_data.active(),
             ),
+            self.theme,
         );
 
         self.popup_data
-            .render_widget(frame, area, FocusState::Highlight);
+            .render_widget(frame, area, FocusState::Highlight, self.theme);
     }
 }
 
-pub fn main_loop(mut files: Vec<File>) -> Result<(), Box<dyn std::error::Error>> {
+/// Commands that apply the pending changes of all groups of the tabs, the changes of
+/// audio and subtitle groups are merged into one command per file.
+fn pending_commands(tabs: [&mut GroupTabData; 2]) -> (Vec<Command>, Vec<String>) {
+    let mut changes = Vec::new();
+    for tab in tabs {
+        let track_type = tab.track_type;
+        changes.extend(
+            tab.pending_changes()
+                .into_iter()
+                .map(|(idx, group, keys)| (track_type, idx, group, keys)),
+        );
+    }
+    let mut preview = Vec::new();
+    let mut plans: Vec<(&File, EditPlan)> = Vec::new();
+    for (track_type, idx, group, keys) in &changes {
+        let type_name = match track_type {
+            TrackType::Audio => "Audio",
+            TrackType::Subtitles => "Subs",
+            TrackType::Video => "Video",
+        };
+        preview.push(format!(
+            "{} group #{}: {} files",
+            type_name,
+            idx,
+            group.files.len()
+        ));
+        let track_ops = group.track_changes(keys, *track_type);
+        for file in &group.files {
+            match plans.iter_mut().find(|(planned, _)| planned == file) {
+                Some((_, plan)) => plan.add_track_ops(&track_ops),
+                None => {
+                    let mut plan = EditPlan::new();
+                    plan.add_track_ops(&track_ops);
+                    plans.push((file, plan));
+                }
+            }
+        }
+    }
+    let commands = plans
+        .iter()
+        .filter(|(_, plan)| !plan.empty())
+        .map(|(file, plan)| plan.generate_command(file))
+        .collect();
+    (commands, preview)
+}
+
+pub fn main_loop(
+    mut files: Vec<File>,
+    policy: DefaultPolicy,
+    scan_errors: usize,
+    mut group_sort: GroupSort,
+    mut file_sort: FileSort,
+    keymap: Keymap,
+    theme: Theme,
+) -> Result<(), Box<dyn std::error::Error>> {
     enable_raw_mode().expect("can run in raw mode");
 
     let (tx, rx) = mpsc::channel();
@@ -486,10 +988,9 @@ pub fn main_loop(mut files: Vec<File>) -> Result<(), Box<dyn std::error::Error>>
                 .checked_sub(last_tick.elapsed())
                 .unwrap_or_else(|| Duration::from_secs(0));
 
-            if event::poll(timeout).expect("poll works")
-                && let CEvent::Key(key) = event::read().expect("can read events")
-            {
-                tx.send(Event::Input(key)).expect("can send events");
+            if event::poll(timeout).expect("poll works") {
+                let event = event::read().expect("can read events");
+                tx.send(Event::Input(event)).expect("can send events");
             }
 
             if last_tick.elapsed() >= tick_rate && tx.send(Event::Tick).is_ok() {
@@ -499,24 +1000,73 @@ pub fn main_loop(mut files: Vec<File>) -> Result<(), Box<dyn std::error::Error>>
     });
 
     let stdout = io::stdout();
-    execute!(io::stdout(), EnterAlternateScreen).unwrap();
+    execute!(io::stdout(), EnterAlternateScreen, EnableMouseCapture).unwrap();
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
     terminal.clear()?;
 
-    let menu_titles = ["Info", "Subs", "Audio", "Quit"];
+    let menu_titles = ["Info", "Subs", "Audio", "Problems", "Quit"];
     let mut active_menu_item = MenuItem::Home;
+    let mut edits_audio = GroupEdits::default();
+    let mut edits_subs = GroupEdits::default();
+    // Position of the menu tabs for mouse clicks
+    let mut tabs_area = Rect::default();
     'outer: loop {
-        let groups_subs = groupby(&files, key_sublang_subname);
-        let groups_audio = groupby(&files, key_audlang_audname);
+        let mut lint_config = LintConfig::default();
+        lint_config.policy = policy.clone();
+        let findings = lint_files(&files, &lint_config);
+        let problem_files: HashSet<&str> = findings
+            .iter()
+            .map(|finding| finding.file_name.as_str())
+            .collect();
+        let has_problems = |file: &File| problem_files.contains(file.file_name.as_str());
+        let mut groups_subs = groupby(&files, key_sublang_subname);
+        sort_groups(&mut groups_subs, group_sort, file_sort, has_problems);
+        let mut groups_audio = groupby(&files, key_audlang_audname);
+        sort_groups(&mut groups_audio, group_sort, file_sort, has_problems);
+        let info_widget = InfoWidget::new(LibraryStats::from_files(
+            &files,
+            scan_errors,
+            &groups_audio,
+            &groups_subs,
+        ));
 
-        let mut audio_tab_data = GroupTabData::new(&groups_audio, TrackType::Audio);
-        let mut sub_tab_data = GroupTabData::new(&groups_subs, TrackType::Subtitles);
+        let mut audio_tab_data = GroupTabData::new(
+            &groups_audio,
+            TrackType::Audio,
+            policy.clone(),
+            std::mem::take(&mut edits_audio),
+            &keymap,
+            &theme,
+        );
+        let mut sub_tab_data = GroupTabData::new(
+            &groups_subs,
+            TrackType::Subtitles,
+            policy.clone(),
+            std::mem::take(&mut edits_subs),
+            &keymap,
+            &theme,
+        );
         // Refresh keys which means that keys are copied to the editable area.
         audio_tab_data.load_selected_group();
         sub_tab_data.load_selected_group();
+        for tab_data in [&mut audio_tab_data, &mut sub_tab_data] {
+            tab_data.group_list.set_sort(group_sort, file_sort);
+        }
+        let mut problems_tab_data = ProblemsTabData::new(&files, findings.clone(), &keymap, &theme);
 
         let mut changed_files = 'inner: loop {
+            let hint = match active_menu_item {
+                MenuItem::Home => keymap.hint(&[
+                    KeyAction::TabSubs,
+                    KeyAction::TabAudio,
+                    KeyAction::TabProblems,
+                    KeyAction::Quit,
+                ]),
+                MenuItem::Subs => sub_tab_data.hint(),
+                MenuItem::Audio => audio_tab_data.hint(),
+                MenuItem::Problems => problems_tab_data.hint(),
+            };
             terminal.draw(|rect| {
                 let size = rect.area();
                 let chunks = Layout::default()
@@ -532,18 +1082,16 @@ pub fn main_loop(mut files: Vec<File>) -> Result<(), Box<dyn std::error::Error>>
                     )
                     .split(size);
 
-                let progressbar = Paragraph::new(
-                    "Press F2 to generate and show the commands that will apply the changes.",
-                )
-                .style(Style::default().fg(Color::LightCyan))
-                .alignment(Alignment::Center)
-                .block(
-                    Block::default()
-                        .borders(Borders::ALL)
-                        .style(Style::default().fg(Color::White))
-                        .title("Progress")
-                        .border_type(BorderType::Plain),
-                );
+                let progressbar = Paragraph::new(hint)
+                    .style(Style::default().fg(theme.hint))
+                    .alignment(Alignment::Center)
+                    .block(
+                        Block::default()
+                            .borders(Borders::ALL)
+                            .style(Style::default().fg(theme.border))
+                            .title("Keys")
+                            .border_type(BorderType::Plain),
+                    );
 
                 let menu: Vec<Line> = menu_titles
                     .iter()
@@ -553,10 +1101,10 @@ pub fn main_loop(mut files: Vec<File>) -> Result<(), Box<dyn std::error::Error>>
                             Span::styled(
                                 first,
                                 Style::default()
-                                    .fg(SEL_COLOR)
+                                    .fg(theme.accent)
                                     .add_modifier(Modifier::UNDERLINED),
                             ),
-                            Span::styled(rest, Style::default().fg(Color::White)),
+                            Span::styled(rest, Style::default().fg(theme.text)),
                         ])
                     })
                     .collect();
@@ -564,36 +1112,63 @@ pub fn main_loop(mut files: Vec<File>) -> Result<(), Box<dyn std::error::Error>>
                 let tabs = Tabs::new(menu)
                     .select::<usize>(active_menu_item.into())
                     .block(Block::default().title("Menu").borders(Borders::ALL))
-                    .style(Style::default().fg(Color::White))
-                    .highlight_style(Style::default().fg(SEL_COLOR))
+                    .style(Style::default().fg(theme.text))
+                    .highlight_style(theme.selection(theme.accent))
                     .divider(Span::raw("|"));
 
                 rect.render_widget(tabs, chunks[0]);
+                tabs_area = chunks[0];
 
                 match active_menu_item {
-                    MenuItem::Home => rect.render_widget(render_home(), chunks[1]),
+                    MenuItem::Home => info_widget.render(rect, chunks[1], &theme),
                     MenuItem::Subs => {
                         sub_tab_data.render(rect, chunks[1]);
                     }
                     MenuItem::Audio => {
                         audio_tab_data.render(rect, chunks[1]);
                     }
+                    MenuItem::Problems => problems_tab_data.render(rect, chunks[1]),
                 }
                 rect.render_widget(progressbar, chunks[2]);
             })?;
 
             match rx.recv()? {
                 Event::Input(event) => {
-                    let action = match active_menu_item {
-                        MenuItem::Subs => sub_tab_data.process_key(event.code),
-                        MenuItem::Audio => audio_tab_data.process_key(event.code),
-                        _ => match event.code {
-                            KeyCode::Char('i') => Action::SwitchTab(MenuItem::Home),
-                            KeyCode::Char('s') => Action::SwitchTab(MenuItem::Subs),
-                            KeyCode::Char('a') => Action::SwitchTab(MenuItem::Audio),
-                            KeyCode::Char('q') => Action::Quit,
-                            _ => Action::Pass,
+                    let action = match event {
+                        CEvent::Key(event) => match active_menu_item {
+                            MenuItem::Subs => sub_tab_data.process_key(event),
+                            MenuItem::Audio => audio_tab_data.process_key(event),
+                            MenuItem::Problems => problems_tab_data.process_key(event),
+                            _ => match keymap.action(event, &[KeyContext::Global]) {
+                                Some(KeyAction::TabInfo) => Action::SwitchTab(MenuItem::Home),
+                                Some(KeyAction::TabSubs) => Action::SwitchTab(MenuItem::Subs),
+                                Some(KeyAction::TabAudio) => Action::SwitchTab(MenuItem::Audio),
+                                Some(KeyAction::TabProblems) => {
+                                    Action::SwitchTab(MenuItem::Problems)
+                                }
+                                Some(KeyAction::Quit) => Action::Quit,
+                                _ => Action::Pass,
+                            },
                         },
+                        CEvent::Mouse(event) => match clicked_tab(tabs_area, &menu_titles, event) {
+                            Some(idx) if event.kind == MouseEventKind::Down(MouseButton::Left) => {
+                                match idx {
+                                    0 => Action::SwitchTab(MenuItem::Home),
+                                    1 => Action::SwitchTab(MenuItem::Subs),
+                                    2 => Action::SwitchTab(MenuItem::Audio),
+                                    3 => Action::SwitchTab(MenuItem::Problems),
+                                    _ => Action::Quit,
+                                }
+                            }
+                            _ => match active_menu_item {
+                                MenuItem::Subs => sub_tab_data.process_mouse(event),
+                                MenuItem::Audio => audio_tab_data.process_mouse(event),
+                                MenuItem::Problems => problems_tab_data.process_mouse(event),
+                                MenuItem::Home => Action::Pass,
+                            },
+                        },
+                        // E.g. resizing, the screen is redrawn anyway
+                        _ => Action::Pass,
                     };
                     match action {
                         Action::Quit => {
@@ -602,9 +1177,37 @@ pub fn main_loop(mut files: Vec<File>) -> Result<(), Box<dyn std::error::Error>>
                         Action::ReloadFiles(changed_files) => {
                             break 'inner changed_files;
                         }
+                        // Regroup the files in the new order, pending changes, the selected
+                        // group and the search query are kept
+                        Action::CycleGroupSort => {
+                            group_sort = group_sort.next();
+                            break 'inner Vec::new();
+                        }
+                        Action::CycleFileSort => {
+                            file_sort = file_sort.next();
+                            break 'inner Vec::new();
+                        }
+                        Action::ApplyPending => {
+                            let (commands, preview) =
+                                pending_commands([&mut sub_tab_data, &mut audio_tab_data]);
+                            let tab_data = match active_menu_item {
+                                MenuItem::Audio => &mut audio_tab_data,
+                                _ => &mut sub_tab_data,
+                            };
+                            if commands.is_empty() {
+                                tab_data.process_action(Action::ShowMessage(
+                                    "No pending changes".to_string(),
+                                ));
+                            } else {
+                                tab_data.show_commands(commands, preview);
+                            }
+                        }
                         Action::SwitchTab(MenuItem::Home) => active_menu_item = MenuItem::Home,
                         Action::SwitchTab(MenuItem::Subs) => active_menu_item = MenuItem::Subs,
                         Action::SwitchTab(MenuItem::Audio) => active_menu_item = MenuItem::Audio,
+                        Action::SwitchTab(MenuItem::Problems) => {
+                            active_menu_item = MenuItem::Problems
+                        }
                         _ => {}
                     }
                 }
@@ -619,6 +1222,10 @@ pub fn main_loop(mut files: Vec<File>) -> Result<(), Box<dyn std::error::Error>>
                             let auto_close_action = audio_tab_data.check_auto_close();
                             audio_tab_data.process_action(auto_close_action)
                         }
+                        MenuItem::Problems => {
+                            let auto_close_action = problems_tab_data.check_auto_close();
+                            problems_tab_data.process_action(auto_close_action)
+                        }
                         _ => Action::Pass,
                     };
                     match action {
@@ -631,11 +1238,16 @@ pub fn main_loop(mut files: Vec<File>) -> Result<(), Box<dyn std::error::Error>>
                         Action::SwitchTab(MenuItem::Home) => active_menu_item = MenuItem::Home,
                         Action::SwitchTab(MenuItem::Subs) => active_menu_item = MenuItem::Subs,
                         Action::SwitchTab(MenuItem::Audio) => active_menu_item = MenuItem::Audio,
+                        Action::SwitchTab(MenuItem::Problems) => {
+                            active_menu_item = MenuItem::Problems
+                        }
                         _ => {}
                     }
                 }
             }
         };
+        edits_audio = audio_tab_data.take_edits();
+        edits_subs = sub_tab_data.take_edits();
         for file in files.iter_mut() {
             if let Some(pos) = changed_files.iter().position(|ch_f| ch_f == file) {
                 let changed_file = changed_files.remove(pos);
@@ -645,34 +1257,140 @@ pub fn main_loop(mut files: Vec<File>) -> Result<(), Box<dyn std::error::Error>>
     }
     disable_raw_mode()?;
     terminal.show_cursor()?;
-    execute!(std::io::stdout(), LeaveAlternateScreen).unwrap();
+    execute!(std::io::stdout(), LeaveAlternateScreen, DisableMouseCapture).unwrap();
 
     Ok(())
 }
 
-fn render_home() -> Paragraph<'static> {
-    Paragraph::new(vec![
-        Line::from(vec![Span::raw("")]),
-        Line::from(vec![Span::raw("Welcome")]),
-        Line::from(vec![Span::raw("")]),
-        Line::from(vec![Span::raw("to")]),
-        Line::from(vec![Span::raw("")]),
-        Line::from(vec![Span::styled(
-            "mtxstuff",
-            Style::default().fg(Color::LightBlue),
-        )]),
-        Line::from(vec![Span::raw("")]),
-        Line::from(vec![Span::raw("Press 'S' to access Subtitle view, 'A' to access audio track view.")]),
-        Line::from(vec![Span::raw("Files are scanned and put into groups that share the same track metadata (name, lang, flags).")]),
-        Line::from(vec![Span::raw("This makes it easy to change metadata on multiple files that share the same general track list shape.")]),
-        Line::from(vec![Span::raw("Changes are applied to all files in a group!")]),
-    ])
-    .alignment(Alignment::Center)
-    .block(
-        Block::default()
-            .borders(Borders::ALL)
-            .style(Style::default().fg(Color::White))
-            .title("Home")
-            .border_type(BorderType::Plain),
-    )
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::group::{groupby, key_audlang_audname, key_sublang_subname};
+
+    fn file(file_name: &str, sub_language: &str) -> File {
+        File::from_json_str(&format!(
+            r#"{{
+                "file_name": "{}",
+                "tracks": [
+                    {{"id": 0, "type": "audio", "properties": {{"language": "jpn",
+                        "default_track": false, "forced_track": false, "enabled_track": true}}}},
+                    {{"id": 1, "type": "subtitles", "properties": {{"language": "{}",
+                        "default_track": false, "forced_track": false, "enabled_track": true}}}}
+                ]
+            }}"#,
+            file_name, sub_language
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_pending_commands() {
+        let files = vec![file("a.mkv", "eng"), file("b.mkv", "ger")];
+        let groups_audio = groupby(&files, key_audlang_audname);
+        let groups_subs = groupby(&files, key_sublang_subname);
+        let keymap = Keymap::default();
+        let theme = Theme::default();
+        let mut audio_tab = GroupTabData::new(
+            &groups_audio,
+            TrackType::Audio,
+            DefaultPolicy::default(),
+            GroupEdits::default(),
+            &keymap,
+            &theme,
+        );
+        let mut sub_tab = GroupTabData::new(
+            &groups_subs,
+            TrackType::Subtitles,
+            DefaultPolicy::default(),
+            GroupEdits::default(),
+            &keymap,
+            &theme,
+        );
+        audio_tab.load_selected_group();
+        sub_tab.load_selected_group();
+        assert!(
+            pending_commands([&mut sub_tab, &mut audio_tab])
+                .0
+                .is_empty()
+        );
+
+        // Both files share the audio group, the subtitle groups differ
+        assert_eq!(groups_audio.len(), 1);
+        assert_eq!(groups_subs.len(), 2);
+        audio_tab.track_table.get_keys_copy_mut()[0].default = true;
+        for idx in 0..groups_subs.len() {
+            sub_tab.group_list.select(Some(idx));
+            sub_tab.load_selected_group();
+            sub_tab.track_table.get_keys_copy_mut()[0].forced = true;
+        }
+
+        let (commands, preview) = pending_commands([&mut sub_tab, &mut audio_tab]);
+        assert_eq!(preview.len(), 3);
+        let mut arguments: Vec<Vec<String>> = commands
+            .into_iter()
+            .map(|command| command.arguments)
+            .collect();
+        arguments.sort();
+        assert_eq!(
+            arguments,
+            ["a.mkv", "b.mkv"].map(|file_name| {
+                [
+                    "--edit",
+                    "track:@2",
+                    "--set",
+                    "flag-forced=1",
+                    "--edit",
+                    "track:@1",
+                    "--set",
+                    "flag-default=1",
+                    file_name,
+                ]
+                .map(str::to_owned)
+                .to_vec()
+            })
+        );
+    }
+
+    #[test]
+    fn test_keep_selection() {
+        let files = vec![
+            file("a.mkv", "eng"),
+            file("b.mkv", "ger"),
+            file("c.mkv", "fra"),
+        ];
+        let mut groups = groupby(&files, key_sublang_subname);
+        let keymap = Keymap::default();
+        let theme = Theme::default();
+        let mut tab = GroupTabData::new(
+            &groups,
+            TrackType::Subtitles,
+            DefaultPolicy::default(),
+            GroupEdits::default(),
+            &keymap,
+            &theme,
+        );
+        let ger = groups
+            .iter()
+            .position(|group| group.files[0].file_name == "b.mkv");
+        tab.group_list.restore("er".to_owned(), ger);
+        tab.load_selected_group();
+        tab.track_table.get_keys_copy_mut()[0].forced = true;
+        let edits = tab.take_edits();
+
+        // The groups are rebuilt in another order
+        groups.reverse();
+        let mut tab = GroupTabData::new(
+            &groups,
+            TrackType::Subtitles,
+            DefaultPolicy::default(),
+            edits,
+            &keymap,
+            &theme,
+        );
+        tab.load_selected_group();
+        let selected = tab.selected_group().unwrap();
+        assert_eq!(selected.files[0].file_name, "b.mkv");
+        assert_eq!(tab.group_list.query(), "er");
+        assert!(tab.track_table.get_keys_copy()[0].forced);
+    }
 }
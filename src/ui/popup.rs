@@ -1,14 +1,18 @@
+use crate::analyze::Suggestion;
 use crate::command::Command;
 use crate::command::CommandHandler;
 use crate::command::CommandHandlerStatus;
+use crate::track_operations::FileOperation;
 
 use super::Action;
 use super::CommandType;
 use super::FocusState;
 use super::KeyPressConsumer;
 use super::centered_rect_fit_text;
+use super::keymap::{KeyAction, KeyContext, Keymap};
+use super::theme::Theme;
 use super::{centered_rect, centered_rect_with_height};
-use crossterm::event::KeyCode;
+use crossterm::event::{KeyCode, MouseEvent, MouseEventKind};
 use ratatui::layout::Alignment;
 use ratatui::text::Text;
 use ratatui::widgets::Clear;
@@ -17,17 +21,18 @@ use ratatui::widgets::Paragraph;
 use ratatui::widgets::Wrap;
 use ratatui::{
     Frame,
-    layout::Rect,
-    style::{Color, Modifier, Style},
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Modifier, Style},
     text::{Line, Span},
     widgets::{Block, BorderType, Borders},
 };
 use std::fs::File;
 use std::io::prelude::*;
+use std::time::Duration;
 
 // TODO: Frame<B: Backend>
 pub(crate) trait PopupRender {
-    fn render_widget(&mut self, frame: &mut Frame, area: Rect, focus: FocusState);
+    fn render_widget(&mut self, frame: &mut Frame, area: Rect, focus: FocusState, theme: &Theme);
 }
 
 pub(crate) trait Popup: PopupRender + KeyPressConsumer {}
@@ -53,7 +58,7 @@ impl PopupRenderer {
 }
 
 impl PopupRender for PopupRenderer {
-    fn render_widget(&mut self, frame: &mut Frame, area: Rect, focus: FocusState) {
+    fn render_widget(&mut self, frame: &mut Frame, area: Rect, focus: FocusState, theme: &Theme) {
         let stack_len = self.popup_stack.len();
         for (i, popup) in self.popup_stack.iter_mut().enumerate() {
             let focus = if focus == FocusState::Highlight && i == stack_len - 1 {
@@ -61,15 +66,23 @@ impl PopupRender for PopupRenderer {
             } else {
                 FocusState::Background
             };
-            popup.render_widget(frame, area, focus);
+            popup.render_widget(frame, area, focus, theme);
         }
     }
 }
 
 impl KeyPressConsumer for PopupRenderer {
-    fn process_key(&mut self, key_code: crossterm::event::KeyCode) -> Action {
+    fn process_key(&mut self, key_event: crossterm::event::KeyEvent, keymap: &Keymap) -> Action {
         if let Some(active_popup) = self.popup_stack.last_mut() {
-            active_popup.process_key(key_code)
+            active_popup.process_key(key_event, keymap)
+        } else {
+            Action::Pass
+        }
+    }
+
+    fn process_mouse(&mut self, mouse_event: MouseEvent) -> Action {
+        if let Some(active_popup) = self.popup_stack.last_mut() {
+            active_popup.process_mouse(mouse_event)
         } else {
             Action::Pass
         }
@@ -79,11 +92,22 @@ impl KeyPressConsumer for PopupRenderer {
 pub(crate) struct CommandPopup {
     pub(crate) commands: Vec<Command>,
     pub(crate) command_strings: Vec<String>,
+    pub(crate) file_ops: Vec<FileOperation>,
+    /// Shown instead of the file operations, e.g. old and new track names.
+    pub(crate) preview: Vec<String>,
+    /// Whether the tag keys toggle file operations.
+    pub(crate) file_ops_enabled: bool,
     pub(crate) scroll: u16,
+    title: String,
+    tags_hint: String,
 }
 
 impl CommandPopup {
-    pub(crate) fn new<B: IntoIterator<Item = Command>>(commands: B) -> Self {
+    pub(crate) fn new<B: IntoIterator<Item = Command>>(
+        commands: B,
+        file_ops: Vec<FileOperation>,
+        keymap: &Keymap,
+    ) -> Self {
         let commands: Vec<Command> = commands.into_iter().collect();
         let command_strings: Vec<_> = commands
             .iter()
@@ -92,28 +116,95 @@ impl CommandPopup {
         CommandPopup {
             commands,
             command_strings,
+            file_ops,
+            preview: Vec::new(),
+            file_ops_enabled: true,
             scroll: Default::default(),
+            title: format!(
+                "Commands Preview - Press {} to apply the changes - or press {} to save commands to mtx_commands.sh - {} to abort",
+                keymap.keys(KeyAction::RunCommands),
+                keymap.keys(KeyAction::SaveCommands),
+                keymap.keys(KeyAction::Back)
+            ),
+            tags_hint: format!(
+                "Tags: {}",
+                keymap.hint(&[
+                    KeyAction::StatisticsTags,
+                    KeyAction::DeleteStatisticsTags,
+                    KeyAction::GlobalTags,
+                    KeyAction::ClearGlobalTags,
+                ])
+            ),
         }
     }
 
-    fn render(&mut self, frame: &mut Frame, area: Rect, focus: FocusState) {
-        let border_style = Style::default().fg(focus.border_color());
+    pub(crate) fn with_preview(mut self, preview: Vec<String>) -> Self {
+        self.preview = preview;
+        self.file_ops_enabled = false;
+        self
+    }
+
+    /// Hides the file operations, for callers that do not handle `ToggleFileOperation`.
+    pub(crate) fn without_file_ops(mut self) -> Self {
+        self.file_ops_enabled = false;
+        self
+    }
+
+    fn render(&mut self, frame: &mut Frame, area: Rect, focus: FocusState, theme: &Theme) {
+        let border_style = Style::default().fg(focus.border_color(theme));
         let block = Block::default()
             .borders(Borders::ALL)
-            .style(Style::default().fg(Color::White))
+            .style(Style::default().fg(theme.text))
             .title(Span::styled(
-                "Commands Preview - Press F2 again to apply the changes - or press Enter to save commands to mtx_commands.sh - Esc to abort",
+                self.title.as_str(),
                 Style::default().add_modifier(Modifier::BOLD),
             ))
             .border_type(BorderType::Thick)
             .border_style(border_style);
 
-        let text: Vec<Line> = self
-            .command_strings
-            .iter()
-            .map(AsRef::as_ref)
-            .map(Line::from)
-            .collect();
+        let file_ops = if self.file_ops.is_empty() {
+            "none".to_owned()
+        } else {
+            self.file_ops
+                .iter()
+                .map(FileOperation::description)
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        let mut text: Vec<Line> = if !self.preview.is_empty() {
+            let mut text: Vec<Line> = self
+                .preview
+                .iter()
+                .map(|line| {
+                    Line::from(Span::styled(
+                        line.as_str(),
+                        Style::default().fg(theme.modified),
+                    ))
+                })
+                .collect();
+            text.push(Line::from(""));
+            text
+        } else if !self.file_ops_enabled {
+            Vec::new()
+        } else {
+            vec![
+                Line::from(Span::styled(
+                    self.tags_hint.as_str(),
+                    Style::default().fg(theme.dimmed),
+                )),
+                Line::from(Span::styled(
+                    format!("File operations: {}", file_ops),
+                    Style::default().fg(theme.dimmed),
+                )),
+                Line::from(""),
+            ]
+        };
+        text.extend(
+            self.command_strings
+                .iter()
+                .map(AsRef::as_ref)
+                .map(Line::from),
+        );
         // text[0]
         //     .0
         //     .push(Span::styled("HELLO", Style::default().fg(Color::Green)));
@@ -141,31 +232,50 @@ impl CommandPopup {
 }
 
 impl PopupRender for CommandPopup {
-    fn render_widget(&mut self, frame: &mut Frame, area: Rect, focus: FocusState) {
-        self.render(frame, area, focus);
+    fn render_widget(&mut self, frame: &mut Frame, area: Rect, focus: FocusState, theme: &Theme) {
+        self.render(frame, area, focus, theme);
     }
 }
 
 impl KeyPressConsumer for CommandPopup {
-    fn process_key(&mut self, key_code: crossterm::event::KeyCode) -> Action {
-        match key_code {
-            KeyCode::Up | KeyCode::Char('k') => {
-                if self.scroll > 0 {
-                    self.scroll -= 1
-                };
+    fn process_key(&mut self, key_event: crossterm::event::KeyEvent, keymap: &Keymap) -> Action {
+        match keymap.action(
+            key_event,
+            &[KeyContext::Navigation, KeyContext::CommandPreview],
+        ) {
+            Some(KeyAction::Up) if self.scroll > 0 => {
+                self.scroll -= 1;
             }
-            KeyCode::Down | KeyCode::Char('j') => {
-                if self.scroll < 1000 {
-                    self.scroll += 1
-                };
+            Some(KeyAction::Down) if self.scroll < 1000 => {
+                self.scroll += 1;
             }
-            KeyCode::F(2) => {
+            Some(KeyAction::RunCommands) => {
                 return Action::RunCommands((CommandType::AlterFiles, self.commands.clone()));
             }
-            KeyCode::Esc => {
+            Some(KeyAction::StatisticsTags) if self.file_ops_enabled => {
+                return Action::ToggleFileOperation(FileOperation::AddStatisticsTags);
+            }
+            Some(KeyAction::DeleteStatisticsTags) if self.file_ops_enabled => {
+                return Action::ToggleFileOperation(FileOperation::DeleteStatisticsTags);
+            }
+            Some(KeyAction::GlobalTags) if self.file_ops_enabled => {
+                // Asks for the tags file unless the global tags are already set
+                return match self
+                    .file_ops
+                    .iter()
+                    .find(|op| matches!(op, FileOperation::SetGlobalTags(_)))
+                {
+                    Some(file_op) => Action::ToggleFileOperation(file_op.clone()),
+                    None => Action::EditGlobalTags,
+                };
+            }
+            Some(KeyAction::ClearGlobalTags) if self.file_ops_enabled => {
+                return Action::ToggleFileOperation(FileOperation::ClearGlobalTags);
+            }
+            Some(KeyAction::Back) => {
                 return Action::ClosePopup;
             }
-            KeyCode::Enter => match self.to_file() {
+            Some(KeyAction::SaveCommands) => match self.to_file() {
                 Ok(_) => return Action::ShowMessage("Commands were saved".to_string()),
                 Err(err) => {
                     return Action::ShowMessage(format!("Commands could not be saved: {}", err));
@@ -175,25 +285,51 @@ impl KeyPressConsumer for CommandPopup {
         }
         Action::Pass
     }
+
+    fn process_mouse(&mut self, mouse_event: MouseEvent) -> Action {
+        match mouse_event.kind {
+            MouseEventKind::ScrollUp => self.scroll = self.scroll.saturating_sub(1),
+            MouseEventKind::ScrollDown if self.scroll < 1000 => self.scroll += 1,
+            _ => {}
+        }
+        Action::Pass
+    }
 }
 
-#[derive(Clone, Default)]
+/// What the edited string is used for.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) enum EditTarget {
+    /// Language or name of the selected track table cell.
+    TrackKey,
+    NameTemplate,
+    /// Path of the XML file with the global tags.
+    GlobalTags,
+}
+
+#[derive(Clone)]
 pub(crate) struct EditPopup {
     pub(crate) input: String,
+    pub(crate) target: EditTarget,
 }
 
 use unicode_width::UnicodeWidthStr;
 
 impl EditPopup {
-    fn render(&mut self, frame: &mut Frame, area: Rect, focus: FocusState) {
+    fn render(&mut self, frame: &mut Frame, area: Rect, focus: FocusState, theme: &Theme) {
         let area = centered_rect_with_height(50, 3, area);
-        let border_style = Style::default().fg(focus.border_color());
+        let border_style = Style::default().fg(focus.border_color(theme));
         let input = Paragraph::new(self.input.as_str())
-            .style(Style::default().fg(Color::White))
+            .style(Style::default().fg(theme.text))
             .block(
                 Block::default()
                     .borders(Borders::ALL)
-                    .title("Edit string")
+                    .title(match self.target {
+                        EditTarget::TrackKey => "Edit string",
+                        EditTarget::NameTemplate => {
+                            "Name template, e.g. {lang_name} {channels} {codec}{forced?\" (Forced)\"}"
+                        }
+                        EditTarget::GlobalTags => "Global tags file (XML)",
+                    })
                     .border_type(BorderType::Thick)
                     .border_style(border_style),
             );
@@ -209,19 +345,20 @@ impl EditPopup {
 }
 
 impl PopupRender for EditPopup {
-    fn render_widget(&mut self, frame: &mut Frame, area: Rect, focus: FocusState) {
-        self.render(frame, area, focus);
+    fn render_widget(&mut self, frame: &mut Frame, area: Rect, focus: FocusState, theme: &Theme) {
+        self.render(frame, area, focus, theme);
     }
 }
 
 impl KeyPressConsumer for EditPopup {
-    fn process_key(&mut self, key_code: crossterm::event::KeyCode) -> Action {
-        match key_code {
+    // Text input does not use the keymap
+    fn process_key(&mut self, key_event: crossterm::event::KeyEvent, _keymap: &Keymap) -> Action {
+        match key_event.code {
             KeyCode::Esc => {
-                return Action::EditStringResult(None);
+                return Action::EditStringResult(self.target, None);
             }
             KeyCode::Enter => {
-                return Action::EditStringResult(Some(self.input.clone()));
+                return Action::EditStringResult(self.target, Some(self.input.clone()));
             }
             KeyCode::Backspace => {
                 self.input.pop();
@@ -241,7 +378,7 @@ pub(crate) struct MessagePopup {
 }
 
 impl MessagePopup {
-    fn render(&mut self, frame: &mut Frame, area: Rect, focus: FocusState) {
+    fn render(&mut self, frame: &mut Frame, area: Rect, focus: FocusState, theme: &Theme) {
         let margin_y = 2;
         let area = centered_rect_fit_text(self.message.as_ref(), 2, margin_y, area);
         let mut spans = Vec::<Line>::new();
@@ -251,9 +388,9 @@ impl MessagePopup {
         }
         spans.push(Line::from(vec![Span::raw(&self.message)]));
         //let input = Paragraph::new(self.message.as_ref())
-        let border_style = Style::default().fg(focus.border_color());
+        let border_style = Style::default().fg(focus.border_color(theme));
         let input = Paragraph::new(spans)
-            .style(Style::default().fg(Color::White))
+            .style(Style::default().fg(theme.text))
             .block(
                 Block::default()
                     .borders(Borders::ALL)
@@ -267,21 +404,230 @@ impl MessagePopup {
 }
 
 impl PopupRender for MessagePopup {
-    fn render_widget(&mut self, frame: &mut Frame, area: Rect, focus: FocusState) {
-        self.render(frame, area, focus);
+    fn render_widget(&mut self, frame: &mut Frame, area: Rect, focus: FocusState, theme: &Theme) {
+        self.render(frame, area, focus, theme);
     }
 }
 
 impl KeyPressConsumer for MessagePopup {
-    fn process_key(&mut self, key_code: crossterm::event::KeyCode) -> Action {
-        match key_code {
-            KeyCode::Esc | KeyCode::Enter => {
+    fn process_key(&mut self, key_event: crossterm::event::KeyEvent, keymap: &Keymap) -> Action {
+        if let Some(KeyAction::Back | KeyAction::Confirm) =
+            keymap.action(key_event, &[KeyContext::Navigation, KeyContext::Popup])
+        {
+            return Action::ClosePopup;
+        }
+        Action::Pass
+    }
+}
+
+/// Lists the key bindings.
+pub(crate) struct HelpPopup {
+    lines: Vec<String>,
+    title: String,
+    scroll: u16,
+}
+
+impl HelpPopup {
+    /// Lists the keys of the contexts.
+    pub(crate) fn new(keymap: &Keymap, contexts: &[KeyContext]) -> Self {
+        HelpPopup {
+            lines: keymap.help(contexts),
+            title: format!("Keys - {} to close", keymap.keys(KeyAction::Back)),
+            scroll: 0,
+        }
+    }
+
+    fn render(&mut self, frame: &mut Frame, area: Rect, focus: FocusState, theme: &Theme) {
+        let border_style = Style::default().fg(focus.border_color(theme));
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .style(Style::default().fg(theme.text))
+            .title(Span::styled(
+                self.title.as_str(),
+                Style::default().add_modifier(Modifier::BOLD),
+            ))
+            .border_type(BorderType::Thick)
+            .border_style(border_style);
+        let text: Vec<Line> = self
+            .lines
+            .iter()
+            .map(|line| {
+                if line.starts_with(' ') {
+                    Line::from(line.as_str())
+                } else {
+                    Line::from(Span::styled(
+                        line.as_str(),
+                        Style::default().add_modifier(Modifier::BOLD),
+                    ))
+                }
+            })
+            .collect();
+        let paragraph = Paragraph::new(text)
+            .block(block)
+            .alignment(Alignment::Left)
+            .scroll((self.scroll, 0));
+        let area = centered_rect(60, 80, area);
+        frame.render_widget(Clear, area);
+        frame.render_widget(paragraph, area);
+    }
+}
+
+impl PopupRender for HelpPopup {
+    fn render_widget(&mut self, frame: &mut Frame, area: Rect, focus: FocusState, theme: &Theme) {
+        self.render(frame, area, focus, theme);
+    }
+}
+
+impl KeyPressConsumer for HelpPopup {
+    fn process_key(&mut self, key_event: crossterm::event::KeyEvent, keymap: &Keymap) -> Action {
+        match keymap.action(
+            key_event,
+            &[
+                KeyContext::Navigation,
+                KeyContext::Popup,
+                KeyContext::Global,
+            ],
+        ) {
+            Some(KeyAction::Up) => self.scroll = self.scroll.saturating_sub(1),
+            Some(KeyAction::Down) if (self.scroll as usize) < self.lines.len() => self.scroll += 1,
+            Some(KeyAction::Back | KeyAction::Confirm | KeyAction::Help | KeyAction::Quit) => {
+                return Action::ClosePopup;
+            }
+            _ => {}
+        }
+        Action::Pass
+    }
+
+    fn process_mouse(&mut self, mouse_event: MouseEvent) -> Action {
+        match mouse_event.kind {
+            MouseEventKind::ScrollUp => self.scroll = self.scroll.saturating_sub(1),
+            MouseEventKind::ScrollDown if (self.scroll as usize) < self.lines.len() => {
+                self.scroll += 1
+            }
+            _ => {}
+        }
+        Action::Pass
+    }
+}
+
+pub(crate) struct SuggestionPopup {
+    pub(crate) suggestions: Vec<Suggestion>,
+    pub(crate) accepted: Vec<bool>,
+    pub(crate) selected: usize,
+    title: String,
+}
+
+impl SuggestionPopup {
+    pub(crate) fn new(suggestions: Vec<Suggestion>, keymap: &Keymap) -> Self {
+        let accepted = vec![true; suggestions.len()];
+        SuggestionPopup {
+            suggestions,
+            accepted,
+            selected: 0,
+            title: format!(
+                "Suggestions - {} to toggle - {} to apply the accepted suggestions - {} to abort",
+                keymap.keys(KeyAction::ToggleSuggestion),
+                keymap.keys(KeyAction::Confirm),
+                keymap.keys(KeyAction::Back)
+            ),
+        }
+    }
+
+    fn render(&mut self, frame: &mut Frame, area: Rect, focus: FocusState, theme: &Theme) {
+        let border_style = Style::default().fg(focus.border_color(theme));
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .style(Style::default().fg(theme.text))
+            .title(Span::styled(
+                self.title.as_str(),
+                Style::default().add_modifier(Modifier::BOLD),
+            ))
+            .border_type(BorderType::Thick)
+            .border_style(border_style);
+
+        let text: Vec<Line> = self
+            .suggestions
+            .iter()
+            .zip(self.accepted.iter())
+            .enumerate()
+            .map(|(idx, (suggestion, accepted))| {
+                let checkbox = if *accepted { "[X]" } else { "[ ]" };
+                let style = if idx == self.selected {
+                    focus.selection(theme)
+                } else {
+                    Style::default()
+                };
+                Line::from(Span::styled(
+                    format!("{} {}", checkbox, suggestion.description()),
+                    style,
+                ))
+            })
+            .collect();
+        let paragraph = Paragraph::new(text)
+            .style(Style::default())
+            .block(block)
+            .alignment(Alignment::Left);
+        let area = centered_rect(80, 60, area);
+        frame.render_widget(Clear, area);
+        frame.render_widget(paragraph, area);
+    }
+}
+
+impl PopupRender for SuggestionPopup {
+    fn render_widget(&mut self, frame: &mut Frame, area: Rect, focus: FocusState, theme: &Theme) {
+        self.render(frame, area, focus, theme);
+    }
+}
+
+impl KeyPressConsumer for SuggestionPopup {
+    fn process_key(&mut self, key_event: crossterm::event::KeyEvent, keymap: &Keymap) -> Action {
+        match keymap.action(
+            key_event,
+            &[
+                KeyContext::Navigation,
+                KeyContext::Popup,
+                KeyContext::Suggestions,
+            ],
+        ) {
+            Some(KeyAction::Up) if self.selected > 0 => {
+                self.selected -= 1;
+            }
+            Some(KeyAction::Down) if self.selected + 1 < self.suggestions.len() => {
+                self.selected += 1;
+            }
+            Some(KeyAction::ToggleSuggestion) => {
+                if let Some(accepted) = self.accepted.get_mut(self.selected) {
+                    *accepted = !*accepted;
+                }
+            }
+            Some(KeyAction::Confirm) => {
+                let accepted: Vec<Suggestion> = self
+                    .suggestions
+                    .iter()
+                    .zip(self.accepted.iter())
+                    .filter(|(_, accepted)| **accepted)
+                    .map(|(suggestion, _)| suggestion.clone())
+                    .collect();
+                return Action::ApplySuggestions(accepted);
+            }
+            Some(KeyAction::Back) => {
                 return Action::ClosePopup;
             }
             _ => {}
         }
         Action::Pass
     }
+
+    fn process_mouse(&mut self, mouse_event: MouseEvent) -> Action {
+        match mouse_event.kind {
+            MouseEventKind::ScrollUp => self.selected = self.selected.saturating_sub(1),
+            MouseEventKind::ScrollDown if self.selected + 1 < self.suggestions.len() => {
+                self.selected += 1
+            }
+            _ => {}
+        }
+        Action::Pass
+    }
 }
 
 pub(crate) struct CommandRunnerPopup<'a> {
@@ -289,31 +635,200 @@ pub(crate) struct CommandRunnerPopup<'a> {
     pub(crate) command_type: CommandType,
     pub(crate) title: String,
     pub(crate) scroll: u16,
+    /// The log scrolls along with new results until it is scrolled by hand.
+    follow: bool,
+    /// Number of finished commands that are in the log.
+    logged: usize,
     pub(crate) results: Option<Vec<Command>>,
     pub(crate) log: Text<'a>,
     pub(crate) error: bool,
+    /// The commands were cancelled while they were running.
+    cancelled: bool,
+    /// Keys that cancel the commands.
+    cancel_keys: String,
     pub(crate) auto_close: bool,
 }
 
+/// Minutes and seconds, with hours for long durations.
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs >= 3600 {
+        format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+    } else {
+        format!("{}:{:02}", secs / 60, secs % 60)
+    }
+}
+
+/// Adds the exit status and the output of a failed command to the log, returns whether it failed.
+fn log_result(log: &mut Text, result: &std::io::Result<Command>, theme: &Theme) -> bool {
+    let mut error = false;
+    match result {
+        Ok(command) => {
+            log.push_line(Line::from(Span::styled(
+                format!("Command: {:}", command.to_cmd_string().unwrap()),
+                Style::default().add_modifier(Modifier::BOLD),
+            )));
+            let Some(output) = command.output.as_ref() else {
+                // Cancelled before it started
+                log.push_line(Line::from(Span::styled(
+                    command.success_string(),
+                    theme.warning_style(),
+                )));
+                log.push_line(Line::from(""));
+                return false;
+            };
+            if !output.status.success() {
+                error = true;
+                log.push_line(Line::from(Span::styled(
+                    command.success_string(),
+                    theme.error_style(),
+                )));
+                for (name, text) in [("stdout", &output.stdout), ("stderr", &output.stderr)] {
+                    if !text.is_empty() {
+                        log.push_line(Line::from(format!("Command output ({}) is:", name)));
+                        for line in text.lines() {
+                            log.push_line(Line::from(Span::styled(
+                                line.to_string(),
+                                Style::default().fg(theme.dimmed),
+                            )));
+                        }
+                    }
+                }
+            } else {
+                log.push_line(Line::from(Span::styled(
+                    command.success_string(),
+                    Style::default().fg(theme.success),
+                )));
+            }
+        }
+        Err(err) => {
+            log.push_line(Line::from(Span::styled(
+                format!("Failed to execute process: {:}", err),
+                theme.error_style(),
+            )));
+            error = true;
+        }
+    }
+    log.push_line(Line::from(""));
+    error
+}
+
+/// Lists which files were changed, skipped because the commands were cancelled or failed.
+fn summary<'a>(
+    results: &[std::io::Result<Command>],
+    done_label: &str,
+    theme: &Theme,
+) -> Vec<Line<'a>> {
+    let mut lines = Vec::new();
+    let (mut done, mut skipped, mut failed) = (0, 0, 0);
+    for result in results {
+        let (label, style, name) = match result {
+            Ok(command) => {
+                let name = command.file_name().unwrap_or_default().to_string();
+                match command.output {
+                    None => {
+                        skipped += 1;
+                        ("skipped", theme.warning_style(), name)
+                    }
+                    Some(ref output) if output.status.success() => {
+                        done += 1;
+                        (done_label, Style::default().fg(theme.success), name)
+                    }
+                    Some(_) => {
+                        failed += 1;
+                        ("failed", theme.error_style(), name)
+                    }
+                }
+            }
+            Err(err) => {
+                failed += 1;
+                ("failed", theme.error_style(), err.to_string())
+            }
+        };
+        lines.push(Line::from(vec![
+            Span::styled(format!("{:<9}", label), style),
+            Span::raw(name),
+        ]));
+    }
+    lines.insert(
+        0,
+        Line::from(Span::styled(
+            format!(
+                "{} {}, {} skipped, {} failed",
+                done, done_label, skipped, failed
+            ),
+            Style::default().add_modifier(Modifier::BOLD),
+        )),
+    );
+    lines.push(Line::from(""));
+    lines
+}
+
 impl<'a> CommandRunnerPopup<'a> {
-    pub(crate) fn new(commands: Vec<Command>, command_type: CommandType, title: String) -> Self {
+    pub(crate) fn new(
+        commands: Vec<Command>,
+        command_type: CommandType,
+        title: String,
+        keymap: &Keymap,
+    ) -> Self {
         CommandRunnerPopup {
             command_handler: Some(CommandHandler::new(commands)),
             command_type,
             title,
             scroll: Default::default(),
+            follow: true,
+            logged: 0,
             results: Default::default(),
             log: Default::default(),
             error: false,
+            cancelled: false,
+            cancel_keys: keymap.keys(KeyAction::Back),
             auto_close: false,
         }
     }
 
-    fn render(&mut self, frame: &mut Frame, area: Rect, focus: FocusState) {
-        let border_style = Style::default().fg(focus.border_color());
+    fn render_log(&mut self, frame: &mut Frame, area: Rect, border_style: Style) {
+        // Two lines and columns of the area are taken by the borders, the wrapped height is
+        // an estimate because words are not split
+        let width = area.width.saturating_sub(2).max(1) as usize;
+        let height: usize = self
+            .log
+            .lines
+            .iter()
+            .map(|line| line.width().div_ceil(width).max(1))
+            .sum();
+        let max_scroll = (height as u16).saturating_sub(area.height.saturating_sub(2));
+        if self.follow || self.scroll >= max_scroll {
+            self.follow = true;
+            self.scroll = max_scroll;
+        }
+        let paragraph = Paragraph::new(self.log.clone())
+            .style(Style::default())
+            .block(
+                Block::default()
+                    .title("Log")
+                    .borders(Borders::ALL)
+                    .border_type(BorderType::Thick)
+                    .border_style(border_style),
+            )
+            .alignment(Alignment::Left)
+            .wrap(Wrap { trim: true })
+            .scroll((self.scroll, 0));
+        frame.render_widget(paragraph, area);
+    }
+
+    fn render(&mut self, frame: &mut Frame, area: Rect, focus: FocusState, theme: &Theme) {
+        let border_style = Style::default().fg(focus.border_color(theme));
         if let Some(mut command_handler) = self.command_handler.take() {
-            match command_handler.check() {
+            let status = command_handler.check();
+            let done = command_handler.done_commands().len();
+            for result in &command_handler.done_commands()[self.logged..] {
+                self.error |= log_result(&mut self.log, result, theme);
+            }
+            self.logged = done;
+            match status {
                 CommandHandlerStatus::Percent(percent) => {
+                    let (done, total) = command_handler.progress();
                     let gauge_box = Gauge::default()
                         .block(
                             Block::default()
@@ -322,97 +837,90 @@ impl<'a> CommandRunnerPopup<'a> {
                                 .border_type(BorderType::Thick)
                                 .border_style(border_style),
                         )
-                        .gauge_style(Style::default().fg(focus.sel_color()))
+                        .gauge_style(Style::default().fg(focus.sel_color(theme)))
+                        .label(format!("{}/{} ({}%)", done, total, percent))
                         .percent(percent);
-                    let area = centered_rect(70, 10, area);
+                    let mut status =
+                        format!("Elapsed {}", format_duration(command_handler.elapsed()));
+                    if let Some(eta) = command_handler.eta() {
+                        status.push_str(&format!(", remaining {}", format_duration(eta)));
+                    }
+                    let cancel_hint = if command_handler.cancelled() {
+                        format!(
+                            "Cancelling, the running command finishes, {} kills it",
+                            self.cancel_keys
+                        )
+                    } else {
+                        format!("{} cancels the remaining commands", self.cancel_keys)
+                    };
+                    let running = Paragraph::new(vec![
+                        Line::from(format!(
+                            "Running: {}",
+                            command_handler.running().unwrap_or("-")
+                        )),
+                        Line::from(Span::styled(status, Style::default().fg(theme.dimmed))),
+                        Line::from(Span::styled(cancel_hint, Style::default().fg(theme.hint))),
+                    ]);
+                    let area = centered_rect(80, 80, area);
+                    let chunks = Layout::default()
+                        .direction(Direction::Vertical)
+                        .constraints([
+                            Constraint::Length(3),
+                            Constraint::Length(3),
+                            Constraint::Min(3),
+                        ])
+                        .split(area);
                     frame.render_widget(Clear, area);
-                    frame.render_widget(gauge_box, area);
+                    frame.render_widget(gauge_box, chunks[0]);
+                    frame.render_widget(running, chunks[1]);
+                    self.render_log(frame, chunks[2], border_style);
                     self.command_handler = Some(command_handler);
                 }
                 CommandHandlerStatus::Done => {
-                    let mut done_commands: Vec<Command> = Vec::new();
-                    self.scroll = 0;
-                    let results: Vec<std::io::Result<Command>> = command_handler.into_results();
-                    for res in results.into_iter() {
-                        match res {
-                            Ok(command) => {
-                                self.log.push_line(Line::from(Span::styled(
-                                    format!("Command: {:}", command.to_cmd_string().unwrap()),
-                                    Style::default().add_modifier(Modifier::BOLD),
-                                )));
-                                let output = command.output.as_ref().expect("command has executed");
-                                if !output.status.success() {
-                                    self.error = true;
-                                    // TODO - get rid of the clones
-                                    self.log.push_line(Line::from(Span::styled(
-                                        command.success_string(),
-                                        Style::default().fg(Color::Red),
-                                    )));
-                                    if !output.stdout.is_empty() {
-                                        self.log
-                                            .push_line(Line::from("Command output (stdout) is:"));
-                                        self.log.push_line(Line::from(Span::styled(
-                                            output.stdout.clone(),
-                                            Style::default().fg(Color::DarkGray),
-                                        )));
-                                    }
-                                    if !output.stderr.is_empty() {
-                                        self.log
-                                            .push_line(Line::from("Command output (stderr) is:"));
-                                        self.log.push_line(Line::from(Span::styled(
-                                            output.stderr.clone(),
-                                            Style::default().fg(Color::DarkGray),
-                                        )));
-                                    }
-                                } else {
-                                    self.log.push_line(Line::from(Span::styled(
-                                        command.success_string(),
-                                        Style::default().fg(Color::Green),
-                                    )));
-                                }
-                                self.log.push_line(Line::from(""));
-                                // put command in results
-                                done_commands.push(command);
-                            }
-                            Err(err) => {
-                                self.log.push_line(Line::from(Span::styled(
-                                    format!("Failed to execute process: {:}", err),
-                                    Style::default().fg(Color::Red),
-                                )));
-                                self.error = true;
-                                break;
-                            }
-                        }
-                    }
-                    self.results = Some(done_commands);
-                    if !self.error {
+                    let results = command_handler.into_results();
+                    if self.error || self.cancelled {
+                        let done_label = match self.command_type {
+                            CommandType::AlterFiles => "changed",
+                            CommandType::ReloadFiles => "reloaded",
+                        };
+                        self.log
+                            .lines
+                            .splice(0..0, summary(&results, done_label, theme));
+                    } else {
                         self.auto_close = true;
                     }
+                    self.results = Some(
+                        results
+                            .into_iter()
+                            .filter_map(|result| result.ok())
+                            .collect(),
+                    );
+                    // The summary and the log of failed commands are read from the start
+                    self.follow = false;
+                    self.scroll = 0;
                 }
             };
-        } else if self.error {
-            let paragraph = Paragraph::new(self.log.clone())
-                .style(Style::default())
-                .block(
-                    Block::default()
-                        .title("Log")
-                        .borders(Borders::ALL)
-                        .border_type(BorderType::Thick)
-                        .border_style(border_style),
-                )
-                .alignment(Alignment::Left)
-                .wrap(Wrap { trim: true })
-                .scroll((self.scroll, 0));
+        } else if self.error || self.cancelled {
             let area = centered_rect(80, 80, area);
             frame.render_widget(Clear, area);
-            frame.render_widget(paragraph, area);
+            self.render_log(frame, area, border_style);
+        }
+    }
+
+    fn scroll(&mut self, up: bool) {
+        if up {
+            self.follow = false;
+            self.scroll = self.scroll.saturating_sub(1);
+        } else {
+            // Scrolling is limited to the end of the log when rendering
+            self.scroll = self.scroll.saturating_add(1);
         }
     }
 }
 
 impl<'a> PopupRender for CommandRunnerPopup<'a> {
-    fn render_widget(&mut self, frame: &mut Frame, area: Rect, focus: FocusState) {
-        self.render(frame, area, focus);
+    fn render_widget(&mut self, frame: &mut Frame, area: Rect, focus: FocusState, theme: &Theme) {
+        self.render(frame, area, focus, theme);
     }
 }
 
@@ -426,26 +934,31 @@ impl<'a> KeyPressConsumer for CommandRunnerPopup<'a> {
         }
     }
 
-    fn process_key(&mut self, key_code: crossterm::event::KeyCode) -> Action {
-        match key_code {
-            KeyCode::Up | KeyCode::Char('k') => {
-                if self.scroll > 0 {
-                    self.scroll -= 1
-                };
-            }
-            KeyCode::Down | KeyCode::Char('j') => {
-                if self.scroll < 1000 {
-                    self.scroll += 1
-                };
-            }
-            KeyCode::Esc => {
-                return if self.results.is_some() {
-                    Action::CommandsDone((self.command_type, self.results.take().unwrap()))
-                } else {
-                    Action::Pass
-                };
+    fn process_key(&mut self, key_event: crossterm::event::KeyEvent, keymap: &Keymap) -> Action {
+        match keymap.action(
+            key_event,
+            &[
+                KeyContext::Navigation,
+                KeyContext::Popup,
+                KeyContext::CommandLog,
+            ],
+        ) {
+            Some(KeyAction::Up) => self.scroll(true),
+            Some(KeyAction::Down) => self.scroll(false),
+            Some(KeyAction::FollowLog) => self.follow = true,
+            Some(KeyAction::Back) => {
+                if let Some(ref command_handler) = self.command_handler {
+                    if command_handler.cancelled() {
+                        command_handler.kill();
+                    } else {
+                        command_handler.cancel();
+                    }
+                    self.cancelled = true;
+                } else if self.results.is_some() {
+                    return Action::CommandsDone((self.command_type, self.results.take().unwrap()));
+                }
             }
-            KeyCode::Enter => {
+            Some(KeyAction::Confirm) => {
                 return if self.results.is_some() {
                     Action::CommandsDone((self.command_type, self.results.take().unwrap()))
                 } else {
@@ -456,4 +969,13 @@ impl<'a> KeyPressConsumer for CommandRunnerPopup<'a> {
         }
         Action::Pass
     }
+
+    fn process_mouse(&mut self, mouse_event: MouseEvent) -> Action {
+        match mouse_event.kind {
+            MouseEventKind::ScrollUp => self.scroll(true),
+            MouseEventKind::ScrollDown => self.scroll(false),
+            _ => {}
+        }
+        Action::Pass
+    }
 }
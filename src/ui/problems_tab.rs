@@ -0,0 +1,339 @@
+use super::FocusState;
+use super::keymap::{KeyAction, KeyContext, Keymap};
+use super::popup::{
+    CommandPopup, CommandRunnerPopup, HelpPopup, MessagePopup, PopupRender, PopupRenderer,
+};
+use super::selectable_state::SelectableState;
+use super::theme::Theme;
+use super::{
+    Action, CommandType, KeyPressConsumer, MenuItem, item_at, reload_commands, reloaded_files,
+};
+use crate::file::{File, TrackType};
+use crate::group::{GroupKey, key_audlang_audname, key_sublang_subname};
+use crate::lint::{Finding, Severity, fix_commands};
+use crossterm::event::{KeyEvent, MouseButton, MouseEvent, MouseEventKind};
+use ratatui::{
+    Frame,
+    layout::Rect,
+    style::Style,
+    text::{Line, Span},
+    widgets::{Block, BorderType, Borders, List, ListItem, ListState},
+};
+
+const CONTEXTS: [KeyContext; 3] = [
+    KeyContext::Problems,
+    KeyContext::Navigation,
+    KeyContext::Global,
+];
+
+/// Lists the lint findings of all files and lets the user pick the fixes to apply.
+pub(crate) struct ProblemsTabData<'a> {
+    files: &'a [File],
+    findings: Vec<Finding>,
+    accepted: Vec<bool>,
+    list_state: ListState,
+    popup_data: PopupRenderer,
+    keymap: &'a Keymap,
+    theme: &'a Theme,
+    /// Where the list was drawn last, used for mouse clicks.
+    area: Rect,
+}
+
+impl SelectableState for ProblemsTabData<'_> {
+    fn select(&mut self, index: Option<usize>) {
+        self.list_state.select(index);
+    }
+
+    fn selected(&self) -> Option<usize> {
+        self.list_state.selected()
+    }
+
+    fn length(&self) -> usize {
+        self.findings.len()
+    }
+}
+
+impl ProblemsTabData<'_> {
+    pub(crate) fn check_auto_close(&mut self) -> Action {
+        self.popup_data.check_auto_close()
+    }
+
+    pub(crate) fn process_key(&mut self, key_event: KeyEvent) -> Action {
+        if self.popup_data.active() {
+            let action = self.popup_data.process_key(key_event, self.keymap);
+            return self.process_action(action);
+        }
+        match self.keymap.action(key_event, &CONTEXTS) {
+            Some(KeyAction::Up) => {
+                self.navigate_up();
+            }
+            Some(KeyAction::Down) => {
+                self.navigate_down();
+            }
+            Some(KeyAction::ToggleFix) => self.toggle_selected(),
+            Some(KeyAction::AcceptFile) => self.accept_file(),
+            Some(KeyAction::AcceptGroup) => self.accept_group(),
+            Some(KeyAction::ApplyFixes) => return self.generate_commands(),
+            Some(KeyAction::Help) => {
+                let contexts = [
+                    KeyContext::Popup,
+                    KeyContext::CommandPreview,
+                    KeyContext::CommandLog,
+                ];
+                let new_popup = HelpPopup::new(self.keymap, &[CONTEXTS, contexts].concat());
+                self.popup_data.popup_stack.push(Box::new(new_popup));
+            }
+            Some(KeyAction::TabInfo) => return Action::SwitchTab(MenuItem::Home),
+            Some(KeyAction::TabSubs) => return Action::SwitchTab(MenuItem::Subs),
+            Some(KeyAction::TabAudio) => return Action::SwitchTab(MenuItem::Audio),
+            Some(KeyAction::TabProblems) => return Action::SwitchTab(MenuItem::Problems),
+            Some(KeyAction::Quit) => return Action::Quit,
+            _ => {}
+        }
+        Action::Pass
+    }
+
+    pub(crate) fn process_mouse(&mut self, mouse_event: MouseEvent) -> Action {
+        if self.popup_data.active() {
+            let action = self.popup_data.process_mouse(mouse_event);
+            return self.process_action(action);
+        }
+        let Some(pos) = item_at(self.area, mouse_event, 0, self.list_state.offset()) else {
+            return Action::Pass;
+        };
+        match mouse_event.kind {
+            MouseEventKind::Down(MouseButton::Left) if pos < self.findings.len() => {
+                self.select(Some(pos));
+                // The checkbox is in the first columns inside the border
+                if mouse_event.column < self.area.x + 1 + 4 {
+                    self.toggle_selected();
+                }
+            }
+            MouseEventKind::ScrollUp => {
+                self.navigate_up();
+            }
+            MouseEventKind::ScrollDown => {
+                self.navigate_down();
+            }
+            _ => {}
+        }
+        Action::Pass
+    }
+}
+
+/// Keys of the tracks a finding is about, used to find the same problem in other files.
+fn finding_keys(file: &File, finding: &Finding) -> Option<Vec<GroupKey>> {
+    match finding.track_type.or(finding.rule.track_type()) {
+        Some(TrackType::Audio) => Some(key_audlang_audname(file)),
+        Some(TrackType::Subtitles) => Some(key_sublang_subname(file)),
+        _ => None,
+    }
+}
+
+impl<'a> ProblemsTabData<'a> {
+    pub(crate) fn new(
+        files: &'a [File],
+        findings: Vec<Finding>,
+        keymap: &'a Keymap,
+        theme: &'a Theme,
+    ) -> Self {
+        let mut new = ProblemsTabData {
+            files,
+            accepted: vec![false; findings.len()],
+            findings,
+            list_state: ListState::default(),
+            popup_data: PopupRenderer {
+                popup_stack: Vec::new(),
+            },
+            keymap,
+            theme,
+            area: Rect::default(),
+        };
+        new.try_enter();
+        new
+    }
+
+    /// Keys shown in the status bar.
+    pub(crate) fn hint(&self) -> String {
+        self.keymap.hint(&[
+            KeyAction::ToggleFix,
+            KeyAction::AcceptFile,
+            KeyAction::AcceptGroup,
+            KeyAction::ApplyFixes,
+            KeyAction::Help,
+        ])
+    }
+
+    fn file(&self, finding: &Finding) -> Option<&'a File> {
+        self.files
+            .iter()
+            .find(|file| file.file_name == finding.file_name)
+    }
+
+    fn toggle_selected(&mut self) {
+        if let Some(selected) = self.selected()
+            && self.findings[selected].fix.is_some()
+        {
+            self.accepted[selected] = !self.accepted[selected];
+        }
+    }
+
+    /// Accepts all fixes of the file of the selected finding.
+    fn accept_file(&mut self) {
+        let Some(selected) = self.selected() else {
+            return;
+        };
+        let file_name = self.findings[selected].file_name.clone();
+        for (finding, accepted) in self.findings.iter().zip(self.accepted.iter_mut()) {
+            if finding.file_name == file_name && finding.fix.is_some() {
+                *accepted = true;
+            }
+        }
+    }
+
+    /// Accepts the fix of the selected finding in all files that have the same
+    /// track layout, i.e. files that would be in the same group.
+    fn accept_group(&mut self) {
+        let Some(selected) = self.selected() else {
+            return;
+        };
+        let finding = &self.findings[selected];
+        let Some(file) = self.file(finding) else {
+            return;
+        };
+        let keys = finding_keys(file, finding);
+        let same_problem: Vec<bool> = self
+            .findings
+            .iter()
+            .map(|other| {
+                other.fix.is_some()
+                    && other.rule == finding.rule
+                    && other.track_type == finding.track_type
+                    && other.track_no == finding.track_no
+                    && self
+                        .file(other)
+                        .is_some_and(|other_file| finding_keys(other_file, other) == keys)
+            })
+            .collect();
+        for (accepted, same) in self.accepted.iter_mut().zip(same_problem) {
+            *accepted |= same;
+        }
+    }
+
+    fn generate_commands(&mut self) -> Action {
+        let accepted = self
+            .findings
+            .iter()
+            .zip(self.accepted.iter())
+            .filter(|(_, accepted)| **accepted)
+            .map(|(finding, _)| finding);
+        let commands = fix_commands(accepted, self.files);
+        if commands.is_empty() {
+            return self.process_action(Action::ShowMessage("No fixes accepted".to_string()));
+        }
+        let command_popup = CommandPopup::new(commands, Vec::new(), self.keymap).without_file_ops();
+        self.popup_data.popup_stack.push(Box::new(command_popup));
+        Action::Pass
+    }
+
+    pub(crate) fn process_action(&mut self, action: Action) -> Action {
+        match action {
+            Action::ShowMessage(string) => {
+                let new_popup = MessagePopup { message: string };
+                self.popup_data.popup_stack.push(Box::new(new_popup));
+            }
+            Action::ClosePopup => {
+                self.popup_data.popup_stack.pop();
+            }
+            Action::RunCommands((command_type, commands)) => {
+                let new_popup = CommandRunnerPopup::new(
+                    commands,
+                    command_type,
+                    "Applying fixes".to_string(),
+                    self.keymap,
+                );
+                self.popup_data.popup_stack.push(Box::new(new_popup));
+            }
+            Action::CommandsDone((CommandType::AlterFiles, commands)) => {
+                self.popup_data.popup_stack.pop();
+                let new_popup = CommandRunnerPopup::new(
+                    reload_commands(&commands),
+                    CommandType::ReloadFiles,
+                    "Reloading files".to_string(),
+                    self.keymap,
+                );
+                self.popup_data.popup_stack.push(Box::new(new_popup));
+            }
+            Action::CommandsDone((CommandType::ReloadFiles, commands)) => {
+                self.popup_data.popup_stack.pop();
+                self.popup_data.popup_stack.pop();
+                return Action::ReloadFiles(reloaded_files(&commands));
+            }
+            switch_tab @ Action::SwitchTab(_) => return switch_tab,
+            Action::Quit => return Action::Quit,
+            _ => {}
+        }
+        Action::Pass
+    }
+
+    pub(crate) fn render(&mut self, frame: &mut Frame, area: Rect) {
+        self.area = area;
+        let theme = self.theme;
+        let focus = if self.popup_data.active() {
+            FocusState::Background
+        } else {
+            FocusState::Highlight
+        };
+        let items: Vec<ListItem> = self
+            .findings
+            .iter()
+            .zip(self.accepted.iter())
+            .map(|(finding, accepted)| {
+                let checkbox = match (&finding.fix, accepted) {
+                    (None, _) => "    ",
+                    (Some(_), false) => "[ ] ",
+                    (Some(_), true) => "[x] ",
+                };
+                let severity_style = match finding.severity {
+                    Severity::Error => theme.error_style(),
+                    Severity::Warning => theme.warning_style(),
+                    _ => Style::default().fg(focus.text_color(theme)),
+                };
+                let mut spans = vec![
+                    Span::raw(checkbox),
+                    Span::styled(format!("{:<8}", finding.severity.id()), severity_style),
+                    Span::raw(format!(
+                        "{} - {}: {}",
+                        finding.file_name,
+                        finding.location(),
+                        finding.message
+                    )),
+                ];
+                if let Some(ref fix) = finding.fix {
+                    spans.push(Span::styled(
+                        format!(" (fix: {})", fix.description),
+                        Style::default().fg(theme.dimmed),
+                    ));
+                }
+                ListItem::new(Line::from(spans))
+            })
+            .collect();
+
+        let num_accepted = self.accepted.iter().filter(|a| **a).count();
+        let title = format!("Problems ({} accepted)", num_accepted);
+        let list = List::new(items)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .style(Style::default().fg(focus.text_color(theme)))
+                    .title(title)
+                    .border_type(BorderType::Plain)
+                    .border_style(Style::default().fg(focus.border_color(theme))),
+            )
+            .highlight_style(focus.selection(theme));
+        frame.render_stateful_widget(list, area, &mut self.list_state);
+
+        self.popup_data
+            .render_widget(frame, area, FocusState::Highlight, theme);
+    }
+}
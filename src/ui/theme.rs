@@ -0,0 +1,268 @@
+use anyhow::{Context, Result, anyhow};
+use ratatui::style::{Color, Modifier, Style};
+use serde::Deserialize;
+use std::path::Path;
+
+pub(crate) const THEMES: [&str; 4] = ["dark", "light", "high-contrast", "mono"];
+
+/// Colors of the TUI. The mono theme only uses the default colors of the terminal and
+/// shows selections in reverse video and errors and warnings with modifiers instead.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Theme {
+    pub(crate) text: Color,
+    /// Inactive widgets, unset values and other less important text.
+    pub(crate) dimmed: Color,
+    pub(crate) border: Color,
+    /// Focused borders, selections and the active tab.
+    pub(crate) accent: Color,
+    /// Text on the accent color of selections.
+    pub(crate) selected_text: Color,
+    /// Pending changes and previews of changes.
+    pub(crate) modified: Color,
+    pub(crate) warning: Color,
+    pub(crate) error: Color,
+    pub(crate) success: Color,
+    /// The key hints at the bottom.
+    pub(crate) hint: Color,
+    /// Background of the rows of a visual selection.
+    pub(crate) visual: Color,
+    pub(crate) reverse_selection: bool,
+    /// Shows errors in bold and warnings underlined, for themes where they share a color.
+    pub(crate) emphasize_severity: bool,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::dark()
+    }
+}
+
+impl Theme {
+    pub(crate) fn dark() -> Self {
+        Theme {
+            text: Color::White,
+            dimmed: Color::DarkGray,
+            border: Color::White,
+            accent: Color::Cyan,
+            selected_text: Color::Black,
+            modified: Color::Yellow,
+            warning: Color::Yellow,
+            error: Color::Red,
+            success: Color::Green,
+            hint: Color::LightCyan,
+            visual: Color::DarkGray,
+            reverse_selection: false,
+            emphasize_severity: false,
+        }
+    }
+
+    pub(crate) fn light() -> Self {
+        Theme {
+            text: Color::Black,
+            dimmed: Color::DarkGray,
+            border: Color::Black,
+            accent: Color::Blue,
+            selected_text: Color::White,
+            modified: Color::Magenta,
+            warning: Color::Magenta,
+            error: Color::Red,
+            success: Color::Green,
+            hint: Color::Blue,
+            visual: Color::Gray,
+            reverse_selection: false,
+            emphasize_severity: false,
+        }
+    }
+
+    pub(crate) fn high_contrast() -> Self {
+        Theme {
+            text: Color::White,
+            dimmed: Color::Gray,
+            border: Color::White,
+            accent: Color::LightYellow,
+            selected_text: Color::Black,
+            modified: Color::LightMagenta,
+            warning: Color::LightYellow,
+            error: Color::LightRed,
+            success: Color::LightGreen,
+            hint: Color::White,
+            visual: Color::Blue,
+            reverse_selection: false,
+            emphasize_severity: false,
+        }
+    }
+
+    pub(crate) fn mono() -> Self {
+        Theme {
+            text: Color::Reset,
+            dimmed: Color::Reset,
+            border: Color::Reset,
+            accent: Color::Reset,
+            selected_text: Color::Reset,
+            modified: Color::Reset,
+            warning: Color::Reset,
+            error: Color::Reset,
+            success: Color::Reset,
+            hint: Color::Reset,
+            visual: Color::Reset,
+            reverse_selection: true,
+            emphasize_severity: true,
+        }
+    }
+
+    pub(crate) fn by_name(name: &str) -> Option<Self> {
+        match name {
+            "dark" => Some(Self::dark()),
+            "light" => Some(Self::light()),
+            "high-contrast" => Some(Self::high_contrast()),
+            "mono" => Some(Self::mono()),
+            _ => None,
+        }
+    }
+
+    /// A built-in theme by name or a theme file.
+    pub(crate) fn from_name_or_path(name: &str) -> Result<Self> {
+        match Self::by_name(name) {
+            Some(theme) => Ok(theme),
+            None => Self::from_path(Path::new(name)),
+        }
+    }
+
+    /// Theme file with a built-in theme as base and the colors that differ from it:
+    ///
+    /// ```toml
+    /// base = "light"
+    /// accent = "#005f87"
+    /// modified = "dark gray"
+    /// ```
+    pub(crate) fn from_str(content: &str) -> Result<Self> {
+        let file: ThemeFile = toml::from_str(content)?;
+        let base = file.base.as_deref().unwrap_or("dark");
+        let mut theme = Self::by_name(base).ok_or_else(|| {
+            anyhow!(
+                "Unknown base theme {}, known themes: {}",
+                base,
+                THEMES.join(", ")
+            )
+        })?;
+        let colors = [
+            (file.text, &mut theme.text),
+            (file.dimmed, &mut theme.dimmed),
+            (file.border, &mut theme.border),
+            (file.accent, &mut theme.accent),
+            (file.selected_text, &mut theme.selected_text),
+            (file.modified, &mut theme.modified),
+            (file.warning, &mut theme.warning),
+            (file.error, &mut theme.error),
+            (file.success, &mut theme.success),
+            (file.hint, &mut theme.hint),
+            (file.visual, &mut theme.visual),
+        ];
+        for (color, theme_color) in colors {
+            if let Some(color) = color {
+                *theme_color = color;
+            }
+        }
+        if let Some(reverse_selection) = file.reverse_selection {
+            theme.reverse_selection = reverse_selection;
+        }
+        if let Some(emphasize_severity) = file.emphasize_severity {
+            theme.emphasize_severity = emphasize_severity;
+        }
+        Ok(theme)
+    }
+
+    pub(crate) fn from_path(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Could not read theme {}", path.display()))?;
+        Self::from_str(&content).with_context(|| format!("Invalid theme {}", path.display()))
+    }
+
+    /// Style of the selected item of a list or table.
+    pub(crate) fn selection(&self, accent: Color) -> Style {
+        let style = Style::default().add_modifier(Modifier::BOLD);
+        if self.reverse_selection {
+            style.add_modifier(Modifier::REVERSED)
+        } else {
+            style.bg(accent).fg(self.selected_text)
+        }
+    }
+
+    pub(crate) fn error_style(&self) -> Style {
+        let style = Style::default().fg(self.error);
+        if self.emphasize_severity {
+            style.add_modifier(Modifier::BOLD)
+        } else {
+            style
+        }
+    }
+
+    pub(crate) fn warning_style(&self) -> Style {
+        let style = Style::default().fg(self.warning);
+        if self.emphasize_severity {
+            style.add_modifier(Modifier::UNDERLINED)
+        } else {
+            style
+        }
+    }
+
+    /// Style of the rows of a visual selection.
+    pub(crate) fn visual_row(&self) -> Style {
+        if self.reverse_selection {
+            Style::default().add_modifier(Modifier::UNDERLINED)
+        } else {
+            Style::default().bg(self.visual)
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ThemeFile {
+    base: Option<String>,
+    text: Option<Color>,
+    dimmed: Option<Color>,
+    border: Option<Color>,
+    accent: Option<Color>,
+    selected_text: Option<Color>,
+    modified: Option<Color>,
+    warning: Option<Color>,
+    error: Option<Color>,
+    success: Option<Color>,
+    hint: Option<Color>,
+    visual: Option<Color>,
+    reverse_selection: Option<bool>,
+    emphasize_severity: Option<bool>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_theme_file() {
+        let theme = Theme::from_str(
+            r##"
+            base = "light"
+            accent = "#005f87"
+            modified = "dark gray"
+            "##,
+        )
+        .unwrap();
+        assert_eq!(theme.accent, Color::Rgb(0x00, 0x5f, 0x87));
+        assert_eq!(theme.modified, Color::DarkGray);
+        assert_eq!(theme.text, Theme::light().text);
+        assert_eq!(Theme::from_str("").unwrap(), Theme::dark());
+        assert!(Theme::from_str("base = \"sepia\"").is_err());
+        assert!(Theme::from_str("accnet = \"red\"").is_err());
+    }
+
+    #[test]
+    fn test_severity_styles() {
+        let mono = Theme::mono();
+        assert_ne!(mono.error_style(), mono.warning_style());
+        let dark = Theme::dark();
+        assert_eq!(dark.error_style(), Style::default().fg(Color::Red));
+        assert_eq!(dark.warning_style(), Style::default().fg(Color::Yellow));
+    }
+}
@@ -1,54 +1,79 @@
 use super::FocusState;
-use crate::group::{Group, GroupKey};
+use crate::file::TrackType;
+use crate::group::{Group, GroupKey, TrackInfo};
 use crate::ui::Action;
 use crate::ui::ActiveWidget;
 use crate::ui::KeyPressConsumer;
+use crate::ui::item_at;
+use crate::ui::keymap::{KeyAction, KeyContext, Keymap};
 use crate::ui::selectable_state::SelectableState;
+use crate::ui::theme::Theme;
 
-use crossterm::event::KeyCode;
-use ratatui::layout::Constraint;
+use crossterm::event::{MouseButton, MouseEvent, MouseEventKind};
+use ratatui::layout::{Constraint, Flex, Layout, Margin};
 use ratatui::widgets::Cell;
 use ratatui::widgets::Row;
 use ratatui::widgets::Table;
 use ratatui::{
     Frame,
     layout::Rect,
-    style::{Color, Modifier, Style},
+    style::{Modifier, Style},
     text::Span,
     widgets::{Block, BorderType, Borders, TableState},
 };
 
+/// Previous and undone states of the edited keys, kept per group when switching groups.
+#[derive(Clone, Default)]
+pub(crate) struct EditHistory {
+    /// The last entry is the most recent one.
+    undo: Vec<Vec<GroupKey>>,
+    redo: Vec<Vec<GroupKey>>,
+}
+
+impl EditHistory {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.undo.is_empty() && self.redo.is_empty()
+    }
+}
+
 #[derive(Clone, Default)]
 pub(crate) struct TrackTableWidget {
     table_state: TableState,
     pub(crate) selected_col: Option<usize>,
     keys_orig: Vec<GroupKey>,
     keys_copy: Vec<GroupKey>,
+    info_headers: Vec<&'static str>,
+    info_rows: Vec<Vec<Option<String>>>,
+    history: EditHistory,
+    /// Row where the visual selection started, edits apply to all rows up to the selected one.
+    visual_anchor: Option<usize>,
+    /// Where the table was drawn last, used for mouse clicks.
+    area: Rect,
 }
 
 impl KeyPressConsumer for TrackTableWidget {
-    fn process_key(&mut self, key_code: crossterm::event::KeyCode) -> Action {
-        match key_code {
-            KeyCode::Up | KeyCode::Char('k') => {
+    fn process_key(&mut self, key_event: crossterm::event::KeyEvent, keymap: &Keymap) -> Action {
+        match keymap.action(key_event, &[KeyContext::Navigation, KeyContext::TrackTable]) {
+            Some(KeyAction::Up) => {
                 self.navigate_up();
             }
-            KeyCode::Down | KeyCode::Char('j') => {
+            Some(KeyAction::Down) => {
                 if let Some(down_res) = self.navigate_down()
                     && !down_res
                 {
                     return Action::NavigateForward(ActiveWidget::Details);
                 }
             }
-            KeyCode::Right | KeyCode::Char('l') => {
+            Some(KeyAction::Right) => {
                 if let Some(selected_col) = self.selected_col {
-                    if selected_col < 4 {
+                    if selected_col < 6 {
                         self.selected_col = Some(selected_col + 1);
                     }
                 } else {
                     self.selected_col = Some(0);
                 }
             }
-            KeyCode::Left | KeyCode::Char('h') => {
+            Some(KeyAction::Left) => {
                 if let Some(selected_col) = self.selected_col {
                     if selected_col > 0 {
                         self.selected_col = Some(selected_col - 1);
@@ -59,16 +84,33 @@ impl KeyPressConsumer for TrackTableWidget {
                     return Action::NavigateBackward(ActiveWidget::Details);
                 }
             }
-            KeyCode::Esc => {
+            Some(KeyAction::Back) => {
                 if self.selected_col.is_some() {
                     self.selected_col = None;
+                } else if self.visual_anchor.is_some() {
+                    self.visual_anchor = None;
                 } else {
                     return Action::NavigateBackward(ActiveWidget::Details);
                 }
             }
-            KeyCode::Enter => {
+            Some(KeyAction::Visual) => {
+                self.visual_anchor = match self.visual_anchor {
+                    Some(_) => None,
+                    None => self.selected(),
+                };
+            }
+            Some(KeyAction::ExclusiveDefault) => {
+                if let Some(sel_row) = self.selected() {
+                    self.set_default_exclusive(sel_row);
+                }
+            }
+            Some(KeyAction::Edit) => {
                 if let Some(selected_col) = self.selected_col {
                     let sel_row = self.selected().unwrap();
+                    if (2..7).contains(&selected_col) {
+                        self.toggle_flag(sel_row, selected_col);
+                        return Action::Pass;
+                    }
                     let gkey = self.keys_copy.get_mut(sel_row).unwrap();
                     match selected_col {
                         0 => {
@@ -85,40 +127,62 @@ impl KeyPressConsumer for TrackTableWidget {
                                 return Action::EditString("".to_owned());
                             }
                         }
-                        2 => {
-                            gkey.default = !gkey.default;
-                        }
-                        3 => {
-                            gkey.forced = !gkey.forced;
-                        }
-                        4 => {
-                            gkey.enabled = !gkey.enabled;
-                        }
                         _ => {}
                     }
                 } else {
                     self.selected_col = Some(0);
                 }
             }
-            KeyCode::Delete => {
+            Some(KeyAction::Clear) => {
                 if let Some(selected_col) = self.selected_col {
-                    let sel_row = self.selected().unwrap();
-                    let gkey = self.keys_copy.get_mut(sel_row).unwrap();
-                    match selected_col {
-                        0 => {
-                            if gkey.language.is_some() {
-                                gkey.language = None
-                            }
-                        }
-                        1 => {
-                            if gkey.name.is_some() {
-                                gkey.name = None
-                            }
+                    self.record();
+                    for row in self.marked_rows() {
+                        let gkey = &mut self.keys_copy[row];
+                        match selected_col {
+                            0 => gkey.language = None,
+                            1 => gkey.name = None,
+                            _ => {}
                         }
-                        _ => {}
                     }
                 }
             }
+            Some(KeyAction::RevertCell) => {
+                if let (Some(sel_row), Some(selected_col)) = (self.selected(), self.selected_col) {
+                    self.revert_cell(sel_row, selected_col);
+                }
+            }
+            Some(KeyAction::RevertRow) => {
+                if let Some(sel_row) = self.selected() {
+                    self.revert_row(sel_row);
+                }
+            }
+            _ => {}
+        }
+        Action::Pass
+    }
+
+    fn process_mouse(&mut self, mouse_event: MouseEvent) -> Action {
+        let Some(row) = item_at(self.area, mouse_event, 1, self.table_state.offset()) else {
+            return Action::Pass;
+        };
+        match mouse_event.kind {
+            MouseEventKind::Down(MouseButton::Left) if row < self.keys_copy.len() => {
+                self.select(Some(row));
+                self.selected_col = self.column_at(mouse_event.column).filter(|col| *col < 7);
+                // Clicking a checkbox toggles the flag
+                if let Some(col) = self.selected_col
+                    && (2..7).contains(&col)
+                {
+                    self.toggle_flag(row, col);
+                }
+                return Action::Focus(ActiveWidget::Details);
+            }
+            MouseEventKind::ScrollUp => {
+                self.navigate_up();
+            }
+            MouseEventKind::ScrollDown => {
+                self.navigate_down();
+            }
             _ => {}
         }
         Action::Pass
@@ -144,21 +208,45 @@ impl SelectableState for TrackTableWidget {
 
     fn leave(&mut self) {
         self.selected_col = None;
+        self.visual_anchor = None;
         self.select(None);
     }
 }
 
+/// Flag shown in the column of the track table.
+fn flag_mut(gkey: &mut GroupKey, col: usize) -> Option<&mut bool> {
+    match col {
+        2 => Some(&mut gkey.default),
+        3 => Some(&mut gkey.forced),
+        4 => Some(&mut gkey.enabled),
+        5 => Some(&mut gkey.hearing_impaired),
+        6 => Some(&mut gkey.commentary),
+        _ => None,
+    }
+}
+
 impl TrackTableWidget {
-    pub(crate) fn from_group(group: Option<&Group>) -> Self {
+    pub(crate) fn from_group(group: Option<&Group>, track_type: TrackType) -> Self {
         let keys_orig = if let Some(sel_group) = group {
             sel_group.key.clone()
         } else {
             Vec::<GroupKey>::new()
         };
+        let info_rows = if let Some(sel_group) = group {
+            sel_group
+                .track_infos(track_type)
+                .iter()
+                .map(|info| info.row(track_type))
+                .collect()
+        } else {
+            Vec::new()
+        };
         let keys_copy = keys_orig.clone();
         Self {
             keys_orig,
             keys_copy,
+            info_headers: TrackInfo::headers(track_type),
+            info_rows,
             ..Self::default()
         }
     }
@@ -171,15 +259,159 @@ impl TrackTableWidget {
         &self.keys_copy
     }
 
+    /// Restores edits that were made before the group was left.
+    pub(crate) fn set_keys_copy(&mut self, keys: Vec<GroupKey>) {
+        self.keys_copy = keys;
+    }
+
+    pub(crate) fn take_history(&mut self) -> EditHistory {
+        std::mem::take(&mut self.history)
+    }
+
+    /// Restores the edit history of the group, see [`TrackTableWidget::set_keys_copy`].
+    pub(crate) fn set_history(&mut self, history: EditHistory) {
+        self.history = history;
+    }
+
+    /// Mutable access to the edited keys, the current state is added to the edit history.
     pub(crate) fn get_keys_copy_mut(&mut self) -> &mut [GroupKey] {
+        self.record();
         &mut self.keys_copy
     }
 
-    pub(crate) fn render(&mut self, frame: &mut Frame, area: Rect, focus: FocusState) {
-        let highlight_style = Style::default()
-            .bg(focus.sel_color())
-            .fg(Color::Black)
-            .add_modifier(Modifier::BOLD);
+    /// Rows that are edited together: the visual selection or only the selected row.
+    pub(crate) fn marked_rows(&self) -> Vec<usize> {
+        match (self.visual_anchor, self.selected()) {
+            (Some(anchor), Some(selected)) => {
+                (anchor.min(selected)..=anchor.max(selected)).collect()
+            }
+            (None, Some(selected)) => vec![selected],
+            _ => Vec::new(),
+        }
+    }
+
+    fn is_marked(&self, row: usize) -> bool {
+        self.marked_rows().contains(&row)
+    }
+
+    /// Toggles a flag column of all marked rows, they all get the inverted value of `row`.
+    fn toggle_flag(&mut self, row: usize, col: usize) {
+        let Some(value) = flag_mut(&mut self.keys_copy[row], col).map(|value| !*value) else {
+            return;
+        };
+        self.record();
+        for marked in self.marked_rows() {
+            if let Some(marked_flag) = flag_mut(&mut self.keys_copy[marked], col) {
+                *marked_flag = value;
+            }
+        }
+    }
+
+    /// Adds the current state to the edit history, must be called before each edit.
+    fn record(&mut self) {
+        self.history.undo.push(self.keys_copy.clone());
+        self.history.redo.clear();
+    }
+
+    /// Returns false if there is nothing to undo.
+    pub(crate) fn undo(&mut self) -> bool {
+        // Skip states that equal the current one, e.g. from edits that changed nothing
+        while let Some(keys) = self.history.undo.pop() {
+            if keys != self.keys_copy {
+                self.history
+                    .redo
+                    .push(std::mem::replace(&mut self.keys_copy, keys));
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Returns false if there is nothing to redo.
+    pub(crate) fn redo(&mut self) -> bool {
+        match self.history.redo.pop() {
+            Some(keys) => {
+                self.history
+                    .undo
+                    .push(std::mem::replace(&mut self.keys_copy, keys));
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn revert_cell(&mut self, row: usize, col: usize) {
+        let (Some(orig), Some(_)) = (self.keys_orig.get(row), self.keys_copy.get(row)) else {
+            return;
+        };
+        let orig = orig.clone();
+        self.record();
+        let gkey = &mut self.keys_copy[row];
+        match col {
+            0 => gkey.language = orig.language,
+            1 => gkey.name = orig.name,
+            2 => gkey.default = orig.default,
+            3 => gkey.forced = orig.forced,
+            4 => gkey.enabled = orig.enabled,
+            5 => gkey.hearing_impaired = orig.hearing_impaired,
+            6 => gkey.commentary = orig.commentary,
+            _ => {}
+        }
+    }
+
+    fn revert_row(&mut self, row: usize) {
+        if let Some(orig) = self.keys_orig.get(row).cloned() {
+            self.record();
+            self.keys_copy[row] = orig;
+        }
+    }
+
+    /// Reverts all edits of the group, this can be undone as well.
+    pub(crate) fn revert_all(&mut self) {
+        self.record();
+        self.keys_copy = self.keys_orig.clone();
+    }
+
+    pub(crate) fn set_default_exclusive(&mut self, row: usize) {
+        self.record();
+        for (idx, gkey) in self.keys_copy.iter_mut().enumerate() {
+            gkey.default = idx == row;
+        }
+    }
+
+    fn column_widths(&self) -> Vec<Constraint> {
+        let mut widths = vec![
+            Constraint::Min(10),
+            Constraint::Min(30),
+            Constraint::Min(5),
+            Constraint::Min(5),
+            Constraint::Min(5),
+            Constraint::Min(5),
+            Constraint::Min(5),
+        ];
+        widths.extend(self.info_headers.iter().map(|_| Constraint::Min(8)));
+        widths
+    }
+
+    /// Column at the screen column, laid out the same way as the table does it.
+    fn column_at(&self, x: u16) -> Option<usize> {
+        Layout::horizontal(self.column_widths())
+            .flex(Flex::Start)
+            .spacing(1)
+            .split(self.area.inner(Margin::new(1, 1)))
+            .iter()
+            .position(|rect| (rect.x..rect.x + rect.width).contains(&x))
+    }
+
+    pub(crate) fn render(
+        &mut self,
+        frame: &mut Frame,
+        area: Rect,
+        focus: FocusState,
+        theme: &Theme,
+    ) {
+        self.area = area;
+        let highlight_style = focus.selection(theme);
 
         let idx_col_to_string = |key_row: &GroupKey, idx_col| match idx_col {
             //0 => if key_row.language != "und" {Some(key_row.language.clone())} else {None},
@@ -200,6 +432,16 @@ impl TrackTableWidget {
             } else {
                 "[ ]".to_owned()
             }),
+            5 => Some(if key_row.hearing_impaired {
+                "[X]".to_owned()
+            } else {
+                "[ ]".to_owned()
+            }),
+            6 => Some(if key_row.commentary {
+                "[X]".to_owned()
+            } else {
+                "[ ]".to_owned()
+            }),
             _ => None,
         };
 
@@ -207,19 +449,16 @@ impl TrackTableWidget {
             let mut style = Style::default();
             if let Some(sel_col) = self.selected_col
                 && sel_col == idx_col
-                && self.selected().unwrap() == idx_row
+                && self.is_marked(idx_row)
             {
-                style = style
-                    .bg(focus.sel_color())
-                    .fg(Color::Black)
-                    .add_modifier(Modifier::BOLD);
+                style = style.patch(focus.selection(theme));
             }
             let keyrow = self.keys_orig.get(idx_row).unwrap();
             if idx_col_to_string(keyrow, idx_col).as_deref() != item {
                 style = style.add_modifier(Modifier::ITALIC);
             }
             if item.is_none() {
-                style = style.fg(Color::DarkGray)
+                style = style.fg(theme.dimmed)
             }
             style
         };
@@ -229,7 +468,7 @@ impl TrackTableWidget {
             .iter()
             .enumerate()
             .map(|(idx_row, keyrow)| {
-                Row::new((0..5).map(|idx_col| {
+                let editable_cells = (0..7).map(|idx_col| {
                     let item = idx_col_to_string(keyrow, idx_col);
                     let text = if let Some(ref item) = item {
                         item.clone()
@@ -238,49 +477,80 @@ impl TrackTableWidget {
                     };
                     let cell = Cell::from(Span::raw(text));
                     cell.style(create_style(item.as_deref(), idx_col, idx_row))
-                }))
+                });
+                // Track info columns are read-only
+                let info_cells = self
+                    .info_rows
+                    .get(idx_row)
+                    .into_iter()
+                    .flatten()
+                    .map(|item| match item {
+                        Some(item) => Cell::from(Span::raw(item.clone())),
+                        None => Cell::from(Span::styled("-", Style::default().fg(theme.dimmed))),
+                    });
+                let row = Row::new(editable_cells.chain(info_cells).collect::<Vec<_>>());
+                if self.visual_anchor.is_some() && self.is_marked(idx_row) {
+                    row.style(theme.visual_row())
+                } else {
+                    row
+                }
             })
             .collect();
-        let border_style = Style::default().fg(focus.border_color());
-
-        let group_detail = Table::new(
-            group_detail_rows,
-            &[
-                Constraint::Min(10),
-                Constraint::Min(30),
-                Constraint::Min(5),
-                Constraint::Min(5),
-                Constraint::Min(5),
-            ],
-        );
+        let border_style = Style::default().fg(focus.border_color(theme));
+
+        let widths = self.column_widths();
+        let info_header_cells = self.info_headers.iter().map(|header| {
+            Cell::from(Span::styled(
+                *header,
+                Style::default().add_modifier(Modifier::BOLD),
+            ))
+        });
+
+        let group_detail = Table::new(group_detail_rows, widths);
         let group_detail = group_detail
-            .header(Row::new(vec![
-                Cell::from(Span::styled(
-                    "lang",
-                    Style::default().add_modifier(Modifier::BOLD),
-                )),
-                Cell::from(Span::styled(
-                    "name",
-                    Style::default().add_modifier(Modifier::BOLD),
-                )),
-                Cell::from(Span::styled(
-                    "def",
-                    Style::default().add_modifier(Modifier::BOLD),
-                )),
-                Cell::from(Span::styled(
-                    "fcd",
-                    Style::default().add_modifier(Modifier::BOLD),
-                )),
-                Cell::from(Span::styled(
-                    "en",
-                    Style::default().add_modifier(Modifier::BOLD),
-                )),
-            ]))
+            .header(Row::new(
+                vec![
+                    Cell::from(Span::styled(
+                        "lang",
+                        Style::default().add_modifier(Modifier::BOLD),
+                    )),
+                    Cell::from(Span::styled(
+                        "name",
+                        Style::default().add_modifier(Modifier::BOLD),
+                    )),
+                    Cell::from(Span::styled(
+                        "def",
+                        Style::default().add_modifier(Modifier::BOLD),
+                    )),
+                    Cell::from(Span::styled(
+                        "fcd",
+                        Style::default().add_modifier(Modifier::BOLD),
+                    )),
+                    Cell::from(Span::styled(
+                        "en",
+                        Style::default().add_modifier(Modifier::BOLD),
+                    )),
+                    Cell::from(Span::styled(
+                        "sdh",
+                        Style::default().add_modifier(Modifier::BOLD),
+                    )),
+                    Cell::from(Span::styled(
+                        "com",
+                        Style::default().add_modifier(Modifier::BOLD),
+                    )),
+                ]
+                .into_iter()
+                .chain(info_header_cells),
+            ))
             .block(
                 Block::default()
                     .borders(Borders::ALL)
-                    .style(Style::default().fg(focus.text_color()))
-                    .title("Detail")
+                    .style(Style::default().fg(focus.text_color(theme)))
+                    .title(if self.visual_anchor.is_some() {
+                        "Detail - VISUAL"
+                    } else {
+                        "Detail"
+                    })
                     .border_type(BorderType::Plain)
                     .border_style(border_style),
             )
@@ -296,3 +566,85 @@ impl TrackTableWidget {
         frame.render_stateful_widget(group_detail, area, &mut self.table_state);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(language: &str, default: bool) -> GroupKey {
+        GroupKey {
+            language: Some(language.to_owned()),
+            name: None,
+            default,
+            forced: false,
+            enabled: true,
+            hearing_impaired: false,
+            commentary: false,
+        }
+    }
+
+    #[test]
+    fn test_undo_redo() {
+        let keys = vec![key("jpn", true), key("eng", false)];
+        let mut table = TrackTableWidget {
+            keys_orig: keys.clone(),
+            keys_copy: keys.clone(),
+            ..TrackTableWidget::default()
+        };
+        assert!(!table.undo());
+
+        table.set_default_exclusive(1);
+        table.get_keys_copy_mut()[0].language = Some("ger".to_owned());
+        let edited = vec![key("ger", false), key("eng", true)];
+        assert_eq!(table.get_keys_copy(), edited);
+
+        assert!(table.undo());
+        assert_eq!(table.get_keys_copy(), [key("jpn", false), key("eng", true)]);
+        assert!(table.undo());
+        assert_eq!(table.get_keys_copy(), keys);
+        assert!(!table.undo());
+        assert!(table.redo());
+        assert!(table.redo());
+        assert_eq!(table.get_keys_copy(), edited);
+        assert!(!table.redo());
+
+        // A new edit drops the undone states
+        assert!(table.undo());
+        table.revert_row(1);
+        assert_eq!(
+            table.get_keys_copy(),
+            [key("jpn", false), key("eng", false)]
+        );
+        assert!(!table.redo());
+
+        // Edits that changed nothing are skipped
+        table.revert_row(1);
+        assert!(table.undo());
+        assert_eq!(table.get_keys_copy(), [key("jpn", false), key("eng", true)]);
+
+        // The history is kept when switching groups
+        let history = table.take_history();
+        assert!(!history.is_empty());
+        assert!(!table.undo());
+        table.set_history(history);
+        assert!(table.undo());
+        assert_eq!(table.get_keys_copy(), keys);
+    }
+
+    #[test]
+    fn test_revert_row() {
+        let keys = vec![key("jpn", true), key("eng", false)];
+        let mut table = TrackTableWidget {
+            keys_orig: keys.clone(),
+            keys_copy: vec![key("ger", false), key("fre", true)],
+            ..TrackTableWidget::default()
+        };
+        table.revert_row(0);
+        assert_eq!(table.get_keys_copy(), [key("jpn", true), key("fre", true)]);
+        // Rows without an original are left alone
+        table.revert_row(2);
+        assert_eq!(table.get_keys_copy(), [key("jpn", true), key("fre", true)]);
+        assert!(table.undo());
+        assert_eq!(table.get_keys_copy(), [key("ger", false), key("fre", true)]);
+    }
+}
@@ -0,0 +1,233 @@
+use crate::command::{CommandHandler, CommandHandlerStatus};
+use crate::file::File;
+use crate::policy::PolicyRules;
+use crate::scan::{IGNORE_FILE_NAME, IgnoreFiles, ScanOptions};
+
+use anyhow::Result;
+use notify::{EventKind, RecursiveMode, Watcher};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+struct PendingFile {
+    last_event: Instant,
+    size: Option<u64>,
+}
+
+/// Files that are being written and files that were just edited by us.
+struct PendingFiles {
+    settle: Duration,
+    pending: HashMap<PathBuf, PendingFile>,
+    // Our own edits trigger events too, these are ignored for the settle time
+    processed: HashMap<PathBuf, Instant>,
+}
+
+impl PendingFiles {
+    fn new(settle: Duration) -> Self {
+        PendingFiles {
+            settle,
+            pending: HashMap::new(),
+            processed: HashMap::new(),
+        }
+    }
+
+    /// Notes a write to the file unless it is one of our own edits.
+    fn written(&mut self, path: PathBuf, now: Instant) {
+        if self
+            .processed
+            .get(&path)
+            .is_some_and(|done| now.duration_since(*done) < self.settle)
+        {
+            return;
+        }
+        self.pending
+            .entry(path)
+            .and_modify(|file| file.last_event = now)
+            .or_insert(PendingFile {
+                last_event: now,
+                size: None,
+            });
+    }
+
+    /// Files that were not written for the settle time and whose size did not change since
+    /// the previous check. `size` returns None for files that are gone.
+    fn settled(&mut self, now: Instant, size: impl Fn(&Path) -> Option<u64>) -> Vec<PathBuf> {
+        let settle = self.settle;
+        let mut settled: Vec<PathBuf> = Vec::new();
+        self.pending.retain(|path, file| {
+            if now.duration_since(file.last_event) < settle {
+                return true;
+            }
+            let Some(size) = size(path) else {
+                // The file is gone, e.g. a temporary file that was renamed
+                return false;
+            };
+            if file.size == Some(size) {
+                settled.push(path.clone());
+                false
+            } else {
+                file.size = Some(size);
+                file.last_event = now;
+                true
+            }
+        });
+        self.processed
+            .retain(|_, done| now.duration_since(*done) < settle);
+        settled
+    }
+
+    fn processed(&mut self, path: PathBuf, now: Instant) {
+        self.processed.insert(path, now);
+    }
+}
+
+/// Prints the message with the time of day in UTC.
+fn log(message: impl AsRef<str>) {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    println!(
+        "[{:02}:{:02}:{:02} UTC] {}",
+        (secs / 3600) % 24,
+        (secs / 60) % 60,
+        secs % 60,
+        message.as_ref()
+    );
+}
+
+/// Watches the directory and applies the rules to every new or changed file once
+/// no write happened for `settle` and its size did not change anymore.
+pub fn watch(
+    dir: &Path,
+    rules: &PolicyRules,
+    scan_options: &ScanOptions,
+    settle: Duration,
+) -> Result<()> {
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx)?;
+    watcher.watch(dir, RecursiveMode::Recursive)?;
+    log(format!("Watching {} for new files", dir.display()));
+
+    let mut pending = PendingFiles::new(settle);
+    let mut ignore_files = IgnoreFiles::default();
+    loop {
+        match rx.recv_timeout(Duration::from_secs(1)) {
+            Ok(Ok(event)) => {
+                if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                    for path in event.paths {
+                        if path
+                            .file_name()
+                            .is_some_and(|name| name == IGNORE_FILE_NAME)
+                        {
+                            // Read the changed ignore file again
+                            ignore_files = IgnoreFiles::default();
+                        }
+                        if scan_options.matches(dir, &path)
+                            && !ignore_files.is_path_ignored(dir, &path)
+                        {
+                            pending.written(path, Instant::now());
+                        }
+                    }
+                    for error in ignore_files.errors.drain(..) {
+                        log(format!("Invalid ignore file {}", error));
+                    }
+                }
+            }
+            Ok(Err(err)) => log(format!("Watch error: {}", err)),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+
+        let settled = pending.settled(Instant::now(), |path| {
+            std::fs::metadata(path).ok().map(|metadata| metadata.len())
+        });
+        for path in settled {
+            process_file(&path, rules);
+            pending.processed(path, Instant::now());
+        }
+    }
+    Ok(())
+}
+
+fn process_file(path: &Path, rules: &PolicyRules) {
+    let file = match File::from_path(path) {
+        Ok(file) => file,
+        Err(err) => {
+            log(format!("Could not identify {}: {:#}", path.display(), err));
+            return;
+        }
+    };
+    let commands = rules.commands(&file);
+    if commands.is_empty() {
+        log(format!("{}: no changes needed", file.file_name));
+        return;
+    }
+    for command in &commands {
+        if let Some(cmd_string) = command.to_cmd_string() {
+            log(format!("Running {}", cmd_string));
+        }
+    }
+    let mut command_handler = CommandHandler::new(commands);
+    while let CommandHandlerStatus::Percent(_) = command_handler.check() {
+        thread::sleep(Duration::from_millis(100));
+    }
+    for result in command_handler.into_results() {
+        match result {
+            Ok(command) => {
+                log(format!("{}: {}", file.file_name, command.success_string()));
+                if let Some(output) = command.output.as_ref()
+                    && !output.status.success()
+                {
+                    log(output.stdout.trim());
+                    log(output.stderr.trim());
+                }
+            }
+            Err(err) => log(format!("Failed to execute process: {}", err)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pending_files() {
+        let settle = Duration::from_secs(2);
+        let secs = |n| Duration::from_secs(n);
+        let start = Instant::now();
+        let path = PathBuf::from("movie.mkv");
+        let mut pending = PendingFiles::new(settle);
+
+        pending.written(path.clone(), start);
+        assert!(pending.settled(start + secs(1), |_| Some(10)).is_empty());
+        // The size is only known after the first check
+        assert!(pending.settled(start + secs(2), |_| Some(10)).is_empty());
+        // The file grew, it is checked again after the settle time
+        assert!(pending.settled(start + secs(4), |_| Some(20)).is_empty());
+        assert!(pending.settled(start + secs(5), |_| Some(20)).is_empty());
+        assert_eq!(
+            pending.settled(start + secs(6), |_| Some(20)),
+            vec![path.clone()]
+        );
+
+        // Writes caused by our own edit are ignored during the settle time
+        pending.processed(path.clone(), start + secs(6));
+        pending.written(path.clone(), start + secs(7));
+        assert!(pending.settled(start + secs(10), |_| Some(20)).is_empty());
+        pending.written(path.clone(), start + secs(10));
+        assert!(pending.settled(start + secs(12), |_| Some(20)).is_empty());
+        assert_eq!(
+            pending.settled(start + secs(14), |_| Some(20)),
+            vec![path.clone()]
+        );
+
+        // Files that are gone are dropped
+        pending.written(path.clone(), start + secs(20));
+        assert!(pending.settled(start + secs(22), |_| None).is_empty());
+        assert!(pending.settled(start + secs(30), |_| Some(20)).is_empty());
+    }
+}